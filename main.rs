@@ -1,224 +1,8398 @@
 use halo2_proofs::{
     arithmetic::{FieldExt, Field},
-    circuit::{Layouter, SimpleFloorPlanner, Value},
-    plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, ConstraintSystem, Error, ProvingKey, VerifyingKey, Selector, Advice, Column, Rotation},
-    poly::{commitment::{Params, ParamsProver}, EvaluationDomain, Polynomial},
-    transcript::{ChallengeScalar, EncodedChallenge, Transcript},
+    circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner, Value},
+    plonk::{create_proof, BatchVerifier, keygen_pk, keygen_vk, verify_proof, Circuit, ConstraintSystem, Error, ProvingKey, VerifyingKey, Selector, Advice, Column, Expression, Instance, Rotation, TableColumn, VirtualCells},
+    plonk::{Any, Assigned, Assignment, Fixed, FloorPlanner},
+    poly::{commitment::{self, Blind, Params, ParamsProver}, Coeff, EvaluationDomain, Polynomial},
+    transcript::{Blake2bRead, Blake2bWrite, Challenge255, ChallengeScalar, EncodedChallenge, Transcript, TranscriptRead, TranscriptWrite},
+};
+use halo2_proofs::arithmetic::{eval_polynomial, Coordinates, CurveAffine};
+use halo2_proofs::dev::MockProver;
+use halo2_proofs::pasta::{EqAffine, Fp};
+use halo2_proofs::pasta::group::{Curve, GroupEncoding};
+use halo2_proofs::pasta::group::ff::PrimeField;
+use halo2_gadgets::poseidon::{
+    primitives::{self as poseidon, ConstantLength, P128Pow5T3},
+    Hash as PoseidonHash, Pow5Chip, Pow5Config,
 };
-use halo2_proofs::pasta::Fp;
 use rand::rngs::OsRng;
-use std::sync::Mutex;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Condvar, Mutex, RwLock};
+use std::thread;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use std::marker::PhantomData;
-use std::collections::HashMap;
-use std::io::{self, Write};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{self, Read, Write};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{compiler_fence, AtomicBool, AtomicU64, Ordering};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use arc_swap::ArcSwap;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use zeroize::Zeroize;
+
+// Id of a stored record, kept distinct from counts and indices. The APIs that create, read back,
+// merge, split and forget whole records take or return it. The store itself still keys on the
+// inner u64, which is also what snapshots and proofs carry, so the prove/verify, metadata and
+// statement APIs take that u64 as it appears in their public inputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct RecordId(u64);
+
+impl From<u64> for RecordId {
+    fn from(id: u64) -> Self {
+        RecordId(id)
+    }
+}
+
+impl From<RecordId> for u64 {
+    fn from(id: RecordId) -> Self {
+        id.0
+    }
+}
+
+impl std::fmt::Display for RecordId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
 
 #[derive(Clone, Debug)]
 struct CompressedData {
     data: Vec<Fp>,
+    checksum: [u8; 32],
+    encoding: Encoding,
+    // Byte length before padding. Under Padding::Zero this, not the element values, is what
+    // separates data from padding on decode, so a record whose real bytes end in zeros
+    // round-trips exactly. The other paddings are self-describing and Padding::strip ignores it.
+    orig_len: usize,
+    padding: Padding,
+    // User labels; not covered by the checksum.
+    metadata: HashMap<String, String>,
+}
+
+impl CompressedData {
+    fn new(data: Vec<Fp>, encoding: Encoding, orig_len: usize) -> Self {
+        let checksum = record_checksum(&data);
+        Self { data, checksum, encoding, orig_len, padding: Padding::Zero, metadata: HashMap::new() }
+    }
+
+    // record_hash of the elements: what record_hash_gadget computes in-circuit, so the statement
+    // proofs can expose it as their public input. prove_matches_hash(id, digest) is the proof of
+    // the record itself that a verifier checks with just the digest. Only the elements go in, so
+    // it survives snapshots and doesn't change with metadata.
+    fn digest(&self) -> Fp {
+        record_hash(&self.data)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Encoding {
+    // One field element per byte
+    Bytewise,
+    // 31 little-endian bytes per element, which always stays below the field modulus
+    Packed,
+    // Packed chunks stored once each, plus an index per chunk; see dictionary_parts
+    Dictionary,
+    // Element 0 is the base record's id (0 for none), then one element per byte of this record
+    // minus the base's byte at that position; see ZKIT::inscribe_delta
+    Delta,
+    // A codec registered with ZKIT::register_codec under this id
+    Custom(u8),
+}
+
+const PACKED_CHUNK: usize = 31;
+// Ids below this are reserved for built-in encodings.
+const CUSTOM_CODEC_MIN: u8 = 16;
+
+impl Encoding {
+    fn id(self) -> u8 {
+        match self {
+            Encoding::Bytewise => 0,
+            Encoding::Packed => 1,
+            Encoding::Dictionary => 2,
+            Encoding::Delta => 3,
+            Encoding::Custom(id) => id,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Encoding::Bytewise),
+            1 => Some(Encoding::Packed),
+            2 => Some(Encoding::Dictionary),
+            3 => Some(Encoding::Delta),
+            id if id >= CUSTOM_CODEC_MIN => Some(Encoding::Custom(id)),
+            _ => None,
+        }
+    }
+}
+
+// How the byte stream is filled out to whole elements before it is compressed. Zero relies on
+// the record's stored byte length; the other two are self-describing, for external encoders.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Padding {
+    #[default]
+    Zero,
+    // The data is preceded by its length as a u64 LE
+    LengthPrefixed,
+    // n bytes of value n complete the last element, a whole element of them when already aligned
+    Pkcs7,
+}
+
+impl Padding {
+    fn id(self) -> u8 {
+        match self {
+            Padding::Zero => 0,
+            Padding::LengthPrefixed => 1,
+            Padding::Pkcs7 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Padding::Zero),
+            1 => Some(Padding::LengthPrefixed),
+            2 => Some(Padding::Pkcs7),
+            _ => None,
+        }
+    }
+
+    // `block` is the number of bytes one element holds.
+    fn apply(self, data: &[u8], block: usize) -> Vec<u8> {
+        match self {
+            Padding::Zero => data.to_vec(),
+            Padding::LengthPrefixed => {
+                let mut out = (data.len() as u64).to_le_bytes().to_vec();
+                out.extend_from_slice(data);
+                out
+            }
+            Padding::Pkcs7 => {
+                let fill = block - data.len() % block;
+                let mut out = data.to_vec();
+                out.resize(data.len() + fill, fill as u8);
+                out
+            }
+        }
+    }
+
+    fn strip(self, mut bytes: Vec<u8>, orig_len: usize, block: usize) -> Result<Vec<u8>, ZkitError> {
+        match self {
+            Padding::Zero => {
+                bytes.truncate(orig_len);
+                Ok(bytes)
+            }
+            Padding::LengthPrefixed => {
+                if bytes.len() < 8 {
+                    return Err(ZkitError::BadPadding);
+                }
+                let len = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize;
+                if bytes.len() - 8 < len {
+                    return Err(ZkitError::BadPadding);
+                }
+                bytes.truncate(8 + len);
+                bytes.drain(..8);
+                Ok(bytes)
+            }
+            Padding::Pkcs7 => {
+                let fill = *bytes.last().ok_or(ZkitError::BadPadding)? as usize;
+                if fill == 0 || fill > block || fill > bytes.len() {
+                    return Err(ZkitError::BadPadding);
+                }
+                if bytes[bytes.len() - fill..].iter().any(|&b| b as usize != fill) {
+                    return Err(ZkitError::BadPadding);
+                }
+                bytes.truncate(bytes.len() - fill);
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+// Bytes one element holds, the block size padding works to. Custom codecs are treated as bytewise.
+fn element_bytes(encoding: Encoding) -> usize {
+    match encoding {
+        Encoding::Packed | Encoding::Dictionary => PACKED_CHUNK,
+        Encoding::Bytewise | Encoding::Delta | Encoding::Custom(_) => 1,
+    }
+}
+
+// Maps ingested bytes to field elements and back. The output of decompress may carry padding;
+// callers strip it according to the record's Padding.
+trait Codec: Send + Sync {
+    fn id(&self) -> u8;
+    fn compress(&self, data: &[u8]) -> Vec<Fp>;
+    fn decompress(&self, data: &[Fp]) -> Vec<u8>;
+}
+
+// Where proofs get their blinding randomness. `stream` counts proofs in the order they start, so
+// a deterministic source can give each one its own sequence.
+trait Randomness: Send + Sync {
+    fn proof_rng(&self, stream: u64) -> Result<Box<dyn RngCore + Send>, ZkitError>;
+}
+
+// The default: 32 bytes of OS entropy key a ChaCha20 stream per proof, so the only draw that can
+// fail is the first one, and that one is retried.
+struct OsRandomness;
+
+impl Randomness for OsRandomness {
+    fn proof_rng(&self, _stream: u64) -> Result<Box<dyn RngCore + Send>, ZkitError> {
+        let mut seed = [0u8; 32];
+        with_retry(|| OsRng.try_fill_bytes(&mut seed), |_| true).map_err(|_| ZkitError::EntropyUnavailable)?;
+        let rng = ChaCha20Rng::from_seed(seed);
+        seed.zeroize();
+        Ok(Box::new(rng))
+    }
+}
+
+// ChaCha20 keyed by the seed, on the numbered stream. Reproducible; for tests and simulations only.
+struct SeededRandomness(u64);
+
+impl Randomness for SeededRandomness {
+    fn proof_rng(&self, stream: u64) -> Result<Box<dyn RngCore + Send>, ZkitError> {
+        let mut rng = ChaCha20Rng::seed_from_u64(self.0);
+        rng.set_stream(stream);
+        Ok(Box::new(rng))
+    }
+}
+
+// A Bytewise element must be below 256; anything else means the record is corrupt.
+fn fp_to_byte(f: &Fp) -> Result<u8, ZkitError> {
+    let repr = f.to_repr();
+    let (low, high) = repr.as_ref().split_at(1);
+    if high.iter().any(|&b| b != 0) {
+        return Err(ZkitError::ValueOutOfRange);
+    }
+    Ok(low[0])
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DecodeMode {
+    // Elements that don't fit the encoding are reported as ValueOutOfRange
+    #[default]
+    Strict,
+    // Oversized elements are truncated to the bytes the encoding keeps, as older releases did
+    Lenient,
+}
+
+// How much structure verify_proof checks beyond the halo2 equations. Strict also requires the
+// transcript to end where halo2 stops reading it. Relaxed accepts trailing bytes, which halo2
+// ignores, so distinct proof byte strings can verify as the same proof: anything that keys on
+// proof bytes (dedup, caches, replay lists) can then be sidestepped by appending junk. Soundness
+// of the statement itself doesn't depend on the mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum VerifyMode {
+    #[default]
+    Strict,
+    Relaxed,
+}
+
+// Strict decoding: each element must fit the encoding's bytes per element. Custom codecs
+// validate their own input.
+fn check_elements(encoding: Encoding, data: &[Fp]) -> Result<(), ZkitError> {
+    let capacity = match encoding {
+        Encoding::Bytewise => 1,
+        Encoding::Packed => PACKED_CHUNK,
+        Encoding::Dictionary => return check_dictionary(data),
+        // Range-checked byte by byte as the deltas are applied
+        Encoding::Delta | Encoding::Custom(_) => return Ok(()),
+    };
+    if data.iter().any(|x| x.to_repr().as_ref()[capacity..].iter().any(|&b| b != 0)) {
+        return Err(ZkitError::ValueOutOfRange);
+    }
+    Ok(())
+}
+
+// Dictionary layout: unique chunk count, chunk count, the unique Packed chunks in first-seen
+// order, then one u32 LE index per chunk, DICTIONARY_INDICES to an element. None if the header
+// doesn't fit the data.
+const DICTIONARY_INDICES: usize = 7;
+
+fn dictionary_parts(data: &[Fp]) -> Option<(&[Fp], Vec<u32>)> {
+    let header = data.get(..2)?;
+    let unique = header[0].get_lower_32() as usize;
+    let chunks = header[1].get_lower_32() as usize;
+    let dictionary = data[2..].get(..unique)?;
+    let indices: Vec<u32> = data[2 + unique..]
+        .iter()
+        .flat_map(|x| {
+            let repr = x.to_repr();
+            repr.as_ref()[..4 * DICTIONARY_INDICES]
+                .chunks(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                .collect::<Vec<_>>()
+        })
+        .take(chunks)
+        .collect();
+    if indices.len() != chunks {
+        return None;
+    }
+    Some((dictionary, indices))
+}
+
+fn check_dictionary(data: &[Fp]) -> Result<(), ZkitError> {
+    let (dictionary, indices) = dictionary_parts(data).ok_or(ZkitError::ValueOutOfRange)?;
+    let fits = |x: &Fp, bytes: usize| x.to_repr().as_ref()[bytes..].iter().all(|&b| b == 0);
+    let index_elements = (indices.len() + DICTIONARY_INDICES - 1) / DICTIONARY_INDICES;
+    if !data[..2].iter().all(|x| fits(x, 4))
+        || !dictionary.iter().all(|x| fits(x, PACKED_CHUNK))
+        || !data[2 + dictionary.len()..].iter().all(|x| fits(x, 4 * DICTIONARY_INDICES))
+        || data.len() != 2 + dictionary.len() + index_elements
+        || indices.iter().any(|&idx| idx as usize >= dictionary.len())
+    {
+        return Err(ZkitError::ValueOutOfRange);
+    }
+    Ok(())
+}
+
+// Legacy conversion: keeps the low byte and silently drops everything above it.
+fn fp_to_byte_lossy(f: &Fp) -> u8 {
+    f.get_lower_32() as u8
+}
+
+// The built-in encodings are codecs themselves. Custom is only a reference to a registered codec,
+// which ZKIT::codec resolves before any of these are called.
+impl Codec for Encoding {
+    fn id(&self) -> u8 {
+        Encoding::id(*self)
+    }
+
+    fn compress(&self, data: &[u8]) -> Vec<Fp> {
+        match self {
+            Encoding::Bytewise => data.iter().map(|&x| Fp::from(x as u64)).collect(),
+            Encoding::Packed => data
+                .chunks(PACKED_CHUNK)
+                .map(|chunk| {
+                    let mut repr = <Fp as PrimeField>::Repr::default();
+                    repr.as_mut()[..chunk.len()].copy_from_slice(chunk);
+                    Fp::from_repr(repr).unwrap()
+                })
+                .collect(),
+            Encoding::Dictionary => {
+                let mut dictionary = Vec::new();
+                let mut positions: HashMap<[u8; 32], u32> = HashMap::new();
+                let indices: Vec<u32> = Encoding::Packed
+                    .compress(data)
+                    .into_iter()
+                    .map(|chunk| {
+                        *positions.entry(chunk.to_repr()).or_insert_with(|| {
+                            dictionary.push(chunk);
+                            dictionary.len() as u32 - 1
+                        })
+                    })
+                    .collect();
+                let mut out = vec![Fp::from(dictionary.len() as u64), Fp::from(indices.len() as u64)];
+                out.extend(dictionary);
+                out.extend(indices.chunks(DICTIONARY_INDICES).map(|group| {
+                    let mut repr = <Fp as PrimeField>::Repr::default();
+                    for (slot, idx) in repr.as_mut().chunks_mut(4).zip(group) {
+                        slot.copy_from_slice(&idx.to_le_bytes());
+                    }
+                    Fp::from_repr(repr).unwrap()
+                }));
+                out
+            }
+            // Without a store to find a base in, deltas are taken from nothing.
+            Encoding::Delta => std::iter::once(Fp::zero()).chain(Encoding::Bytewise.compress(data)).collect(),
+            Encoding::Custom(id) => panic!("encoding {} has no built-in codec", id),
+        }
+    }
+
+    fn decompress(&self, data: &[Fp]) -> Vec<u8> {
+        match self {
+            Encoding::Bytewise => data.iter().map(fp_to_byte_lossy).collect(),
+            Encoding::Packed => data
+                .iter()
+                .flat_map(|x| x.to_repr().as_ref()[..PACKED_CHUNK].to_vec())
+                .collect(),
+            // Lenient like the others: a malformed layout decodes to nothing and an out-of-range
+            // index to a zero chunk. check_elements rejects both in strict mode.
+            Encoding::Dictionary => match dictionary_parts(data) {
+                Some((dictionary, indices)) => indices
+                    .iter()
+                    .flat_map(|&idx| {
+                        let chunk = dictionary.get(idx as usize).copied().unwrap_or(Fp::zero());
+                        chunk.to_repr().as_ref()[..PACKED_CHUNK].to_vec()
+                    })
+                    .collect(),
+                None => Vec::new(),
+            },
+            // Only right for a record with no base; ZKIT::decode_record resolves the others.
+            Encoding::Delta => data.get(1..).map_or_else(Vec::new, |deltas| Encoding::Bytewise.decompress(deltas)),
+            Encoding::Custom(id) => panic!("encoding {} has no built-in codec", id),
+        }
+    }
+}
+
+// Snapshots before v3 don't record the byte length, so recover it the way decoding used to:
+// packed records treat trailing zero bytes as padding. Those versions predate custom codecs.
+fn legacy_orig_len(encoding: Encoding, data: &[Fp]) -> usize {
+    let mut bytes = encoding.decompress(data);
+    if encoding == Encoding::Packed {
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+    }
+    bytes.len()
+}
+
+fn record_checksum(data: &[Fp]) -> [u8; 32] {
+    let mut state = blake2b_simd::Params::new()
+        .hash_length(32)
+        .personal(b"ZKIT_Record_Sum_")
+        .to_state();
+    for x in data {
+        state.update(x.to_repr().as_ref());
+    }
+    state.finalize().as_bytes().try_into().unwrap()
+}
+
+impl Zeroize for CompressedData {
+    fn zeroize(&mut self) {
+        zeroize_elements(&mut self.data);
+    }
+}
+
+// Field elements don't implement Zeroize, so wipe them with volatile writes the compiler can't elide.
+fn zeroize_elements<F: FieldExt>(data: &mut Vec<F>) {
+    for x in data.iter_mut() {
+        unsafe { std::ptr::write_volatile(x, F::zero()) };
+    }
+    compiler_fence(Ordering::SeqCst);
+    data.clear();
+}
+
+#[derive(Debug)]
+enum ZkitError {
+    Halo2(Error),
+    BadMagic,
+    Truncated,
+    UnsupportedVersion(u8),
+    NotFound(u64),
+    LengthMismatch,
+    EmptyRecord(u64),
+    MalformedProof,
+    BadInput { position: usize, token: String },
+    InputTooLong { limit: usize },
+    KeysNotSetUp,
+    WorkerFailed,
+    Config(String),
+    Io(io::Error),
+    CorruptSnapshot,
+    ChecksumMismatch(u64),
+    Expired { age: u64, max_age: u64 },
+    RecordTooLarge { limit: usize },
+    VkMismatch,
+    KMismatch { proof_k: u32, params_k: u32 },
+    EmptyProof,
+    UnknownCodec(u8),
+    ReservedCodecId(u8),
+    BadParams(String),
+    StaleProof(u64),
+    IndexOutOfRange { index: usize, len: usize },
+    NotSorted { index: usize },
+    ValueOutOfRange,
+    UnsupportedEncoding(Encoding),
+    InvalidUtf8 { position: usize },
+    IdInUse(u64),
+    EntropyUnavailable,
+    BadPackage(String),
+    BadPadding,
+    ShuttingDown,
+    ShutdownTimeout { in_flight: usize },
+    ArityMismatch { columns: usize, inputs: usize },
+    NotMerged(u64),
+    BadHex { position: usize },
+    OutOfBounds { index: usize },
+    BadSignature,
+    EmptyBatch,
+    BadSchemaWidth(usize),
+    SchemaViolation { field: usize },
+    NoMatchingRecord,
+    MockCheckFailed(String),
+    ValuePresent(u64),
+    PrefixMismatch { index: usize },
+    DegreeTooSmall { min: u32 },
+    NotConcatenation { index: usize },
+    DecryptionFailed,
+    NotBefore { id: u64, threshold: u64 },
+    EncryptedSnapshot,
+    UnsupportedPadding(Padding),
+    IdsExhausted,
+}
+
+impl From<Error> for ZkitError {
+    fn from(e: Error) -> Self {
+        ZkitError::Halo2(e)
+    }
+}
+
+impl std::fmt::Display for ZkitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZkitError::Halo2(e) => write!(f, "halo2 error: {:?}", e),
+            ZkitError::BadMagic => write!(f, "not a ZKIT proof frame"),
+            ZkitError::Truncated => write!(f, "proof frame is truncated"),
+            ZkitError::UnsupportedVersion(v) => write!(f, "unsupported proof format version {}", v),
+            ZkitError::NotFound(id) => write!(f, "no record with id {}", id),
+            ZkitError::LengthMismatch => write!(f, "records have different lengths"),
+            ZkitError::EmptyRecord(id) => write!(f, "record {} is empty", id),
+            ZkitError::MalformedProof => write!(f, "proof bytes are malformed"),
+            ZkitError::BadInput { position, token } => {
+                write!(f, "invalid byte {:?} at position {}", token, position)
+            }
+            ZkitError::InputTooLong { limit } => write!(f, "input exceeds {} bytes", limit),
+            ZkitError::KeysNotSetUp => write!(f, "keys have not been set up"),
+            ZkitError::WorkerFailed => write!(f, "proving worker exited without a result"),
+            ZkitError::Config(msg) => write!(f, "invalid config: {}", msg),
+            ZkitError::Io(e) => write!(f, "i/o error: {}", e),
+            ZkitError::CorruptSnapshot => write!(f, "snapshot is malformed"),
+            ZkitError::ChecksumMismatch(id) => write!(f, "record {} does not match its checksum", id),
+            ZkitError::RecordTooLarge { limit } => write!(f, "record exceeds the limit of {}", limit),
+            ZkitError::EmptyProof => write!(f, "proof is empty or too short"),
+            ZkitError::BadParams(msg) => write!(f, "invalid params: {}", msg),
+            ZkitError::IndexOutOfRange { index, len } => {
+                write!(f, "index {} is out of range for a record of {} elements", index, len)
+            }
+            ZkitError::NotSorted { index } => {
+                write!(f, "element {} is smaller than its predecessor or too far above it", index)
+            }
+            ZkitError::ValueOutOfRange => write!(f, "field element is not a byte value"),
+            ZkitError::UnsupportedEncoding(encoding) => {
+                write!(f, "operation needs a bytewise record, not {:?}", encoding)
+            }
+            ZkitError::InvalidUtf8 { position } => write!(f, "invalid UTF-8 at byte {}", position),
+            ZkitError::IdInUse(id) => write!(f, "id {} already holds a different record", id),
+            ZkitError::EntropyUnavailable => write!(f, "system randomness unavailable after {} attempts", RETRY_ATTEMPTS),
+            ZkitError::BadPackage(msg) => write!(f, "invalid verifier package: {}", msg),
+            ZkitError::BadPadding => write!(f, "record padding is malformed"),
+            ZkitError::ShuttingDown => write!(f, "instance is shutting down and takes no new jobs"),
+            ZkitError::ShutdownTimeout { in_flight } => {
+                write!(f, "shutdown timed out with {} jobs still running", in_flight)
+            }
+            ZkitError::IdsExhausted => write!(f, "the id counter is at u64::MAX; no more ids can be allocated"),
+            ZkitError::UnsupportedPadding(padding) => {
+                write!(f, "statements about record values need zero padding, not {:?}", padding)
+            }
+            ZkitError::EncryptedSnapshot => write!(f, "encrypted snapshots can't be diffed: their checksums are sealed"),
+            ZkitError::NotBefore { id, threshold } => write!(f, "record {} is not before id {}", id, threshold),
+            ZkitError::DecryptionFailed => write!(f, "record could not be decrypted: wrong key or corrupted ciphertext"),
+            ZkitError::NotConcatenation { index } => {
+                write!(f, "record is not the concatenation of the other two (first difference at element {})", index)
+            }
+            ZkitError::DegreeTooSmall { min } => write!(f, "params degree must be at least k={}", min),
+            ZkitError::PrefixMismatch { index } => write!(f, "record differs from the prefix at byte {}", index),
+            ZkitError::ValuePresent(id) => write!(f, "record {} contains the value", id),
+            ZkitError::MockCheckFailed(msg) => write!(f, "proof verified but the circuit check failed: {}", msg),
+            ZkitError::NoMatchingRecord => write!(f, "no stored record satisfies the predicate"),
+            ZkitError::SchemaViolation { field } => write!(f, "field {} does not satisfy the schema", field),
+            ZkitError::BadSchemaWidth(width) => {
+                write!(f, "schema width {} is outside 2..={}", width, MAX_SCHEMA_WIDTH)
+            }
+            ZkitError::EmptyBatch => write!(f, "batch has no items"),
+            ZkitError::BadSignature => write!(f, "proof signature does not verify under the given key"),
+            ZkitError::OutOfBounds { index } => write!(f, "element {} is outside the claimed bounds", index),
+            ZkitError::BadHex { position } => write!(f, "invalid hex at character {}", position),
+            ZkitError::NotMerged(id) => write!(f, "record {} carries no valid merge boundaries", id),
+            ZkitError::ArityMismatch { columns, inputs } => {
+                write!(f, "{} public inputs don't fill {} instance columns evenly", inputs, columns)
+            }
+            ZkitError::StaleProof(id) => write!(f, "cached proof for record {} no longer matches it", id),
+            ZkitError::UnknownCodec(id) => write!(f, "no codec registered for id {}", id),
+            ZkitError::ReservedCodecId(id) => {
+                write!(f, "codec id {} is reserved; custom ids start at {}", id, CUSTOM_CODEC_MIN)
+            }
+            ZkitError::VkMismatch => write!(f, "proof was made for a different verifying key"),
+            ZkitError::KMismatch { proof_k, params_k } => {
+                write!(f, "proof was made at k={} but params are k={}", proof_k, params_k)
+            }
+            ZkitError::Expired { age, max_age } => {
+                write!(f, "proof is {}s old, older than the allowed {}s", age, max_age)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ZkitError {
+    fn from(e: io::Error) -> Self {
+        ZkitError::Io(e)
+    }
+}
+
+impl std::error::Error for ZkitError {}
+
+// Framed proof layout: magic | version (u8) | k (u32 LE) | [timestamp (u64 LE), v3 only] |
+// transcript length (u32 LE) | transcript. Version 1 is the bare halo2 transcript that create_proof
+// has always returned; v3 adds a unix timestamp that is also absorbed into the transcript.
+const PROOF_MAGIC: [u8; 4] = *b"ZKIT";
+const PROOF_VERSION_RAW: u8 = 1;
+const PROOF_VERSION: u8 = 2;
+const PROOF_VERSION_TIMESTAMPED: u8 = 3;
+const PROOF_HEADER_LEN: usize = 4 + 1 + 4 + 4;
+const MIN_PROOF_LEN: usize = 32;
+
+// zstd frames start with this magic. An unframed v1 transcript could begin with the same four
+// bytes, but only by a 2^-32 accident of its first point encoding.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+// Bounds decompression so a small hostile frame can't expand without limit.
+const MAX_PROOF_BYTES: usize = 16 << 20;
+
+fn compress_proof(proof: &[u8]) -> Vec<u8> {
+    zstd::bulk::compress(proof, 0).expect("compressing into a Vec cannot fail")
+}
+
+fn decompress_proof(compressed: &[u8]) -> Result<Vec<u8>, ZkitError> {
+    zstd::bulk::decompress(compressed, MAX_PROOF_BYTES).map_err(|_| ZkitError::MalformedProof)
+}
+
+// Proofs that aren't zstd frames are passed through untouched.
+fn expand_proof(proof: &[u8]) -> Result<Cow<'_, [u8]>, ZkitError> {
+    if proof.starts_with(&ZSTD_MAGIC) {
+        decompress_proof(proof).map(Cow::Owned)
+    } else {
+        Ok(Cow::Borrowed(proof))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FramedProof {
+    version: u8,
+    k: u32,
+    timestamp: Option<u64>,
+    transcript: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ProofInfo {
+    version: u8,
+    k: Option<u32>,
+    timestamp: Option<u64>,
+    transcript_len: usize,
+}
+
+fn write_proof(k: u32, transcript: &[u8]) -> Vec<u8> {
+    frame_proof(PROOF_VERSION, k, None, transcript)
+}
+
+fn write_timestamped_proof(k: u32, timestamp: u64, transcript: &[u8]) -> Vec<u8> {
+    frame_proof(PROOF_VERSION_TIMESTAMPED, k, Some(timestamp), transcript)
+}
+
+fn frame_proof(version: u8, k: u32, timestamp: Option<u64>, transcript: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(PROOF_HEADER_LEN + 8 + transcript.len());
+    out.extend_from_slice(&PROOF_MAGIC);
+    out.push(version);
+    out.extend_from_slice(&k.to_le_bytes());
+    if let Some(timestamp) = timestamp {
+        out.extend_from_slice(&timestamp.to_le_bytes());
+    }
+    out.extend_from_slice(&(transcript.len() as u32).to_le_bytes());
+    out.extend_from_slice(transcript);
+    out
+}
+
+fn read_proof(bytes: &[u8]) -> Result<FramedProof, ZkitError> {
+    if bytes.len() < PROOF_MAGIC.len() || bytes[..4] != PROOF_MAGIC {
+        return Err(ZkitError::BadMagic);
+    }
+    if bytes.len() < PROOF_HEADER_LEN {
+        return Err(ZkitError::Truncated);
+    }
+    // Every slice below is within the lengths checked above, so the conversions can't fail.
+    let version = bytes[4];
+    let k = u32::from_le_bytes(bytes[5..9].try_into().unwrap());
+    let (timestamp, rest) = match version {
+        PROOF_VERSION => (None, &bytes[9..]),
+        PROOF_VERSION_TIMESTAMPED => {
+            if bytes.len() < PROOF_HEADER_LEN + 8 {
+                return Err(ZkitError::Truncated);
+            }
+            (Some(u64::from_le_bytes(bytes[9..17].try_into().unwrap())), &bytes[17..])
+        }
+        _ => return Err(ZkitError::UnsupportedVersion(version)),
+    };
+    let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+    let transcript = rest[4..].to_vec();
+    if transcript.len() != len {
+        return Err(ZkitError::Truncated);
+    }
+    Ok(FramedProof {
+        version,
+        k,
+        timestamp,
+        transcript,
+    })
+}
+
+// What a router needs to pick a verifier, read from the frame header alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ProofHeader {
+    version: u8,
+    k: u32,
+    // Transcript length the header declares; the transcript itself needn't be present.
+    proof_len: usize,
+}
+
+fn peek_proof_header(bytes: &[u8]) -> Result<ProofHeader, ZkitError> {
+    if bytes.len() < PROOF_MAGIC.len() || bytes[..4] != PROOF_MAGIC {
+        return Err(ZkitError::BadMagic);
+    }
+    let header_len = match bytes.get(4) {
+        Some(&PROOF_VERSION) => PROOF_HEADER_LEN,
+        Some(&PROOF_VERSION_TIMESTAMPED) => PROOF_HEADER_LEN + 8,
+        Some(&version) => return Err(ZkitError::UnsupportedVersion(version)),
+        None => return Err(ZkitError::Truncated),
+    };
+    if bytes.len() < header_len {
+        return Err(ZkitError::Truncated);
+    }
+    Ok(ProofHeader {
+        version: bytes[4],
+        k: u32::from_le_bytes(bytes[5..9].try_into().unwrap()),
+        proof_len: u32::from_le_bytes(bytes[header_len - 4..header_len].try_into().unwrap()) as usize,
+    })
+}
+
+fn proof_info(bytes: &[u8]) -> Result<ProofInfo, ZkitError> {
+    if !bytes.starts_with(&PROOF_MAGIC) {
+        return Ok(ProofInfo {
+            version: PROOF_VERSION_RAW,
+            k: None,
+            timestamp: None,
+            transcript_len: bytes.len(),
+        });
+    }
+    let framed = read_proof(bytes)?;
+    Ok(ProofInfo {
+        version: framed.version,
+        k: Some(framed.k),
+        timestamp: framed.timestamp,
+        transcript_len: framed.transcript.len(),
+    })
+}
+
+// Everything a verifier needs alongside the proof bytes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct ProofArtifact {
+    #[serde(with = "hex_bytes")]
+    proof: Vec<u8>,
+    #[serde(with = "fp_hex")]
+    public_inputs: Vec<Fp>,
+    #[serde(with = "hex_bytes")]
+    vk_fingerprint: Vec<u8>,
+    params_k: u32,
+    created_at: u64,
+}
+
+// An Ed25519 public key in its 32-byte encoding.
+type VerifyingKeyBytes = [u8; 32];
+
+// Signatures cover this prefix followed by the proof bytes, so they can't be replayed as
+// signatures over anything else the key signs.
+const SIGNED_PROOF_CONTEXT: &[u8] = b"ZKIT_Signed_Proof_v1";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SignedProof {
+    proof: Vec<u8>,
+    signature: [u8; 64],
+}
+
+// Requirements for verify_with_policy on top of the proof verifying; None skips a check.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct VerifyPolicy {
+    // The proof's embedded timestamp may be at most this old; untimestamped proofs fail.
+    max_age: Option<Duration>,
+    // The proof must carry an Ed25519 signature by this key, as sign_proof makes.
+    signer: Option<VerifyingKeyBytes>,
+    // The instance's vk must have this fingerprint.
+    vk_fingerprint: Option<[u8; 32]>,
+    // The proof must have been made with this seed; see create_proof_with_seed, or
+    // create_proof_timestamped_with_seed when max_age is also set.
+    context: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum PolicyCheck {
+    // The halo2 proof, under the policy's context if it has one
+    Valid,
+    Fresh,
+    Signed,
+    VkPinned,
+}
+
+// Every check the policy enabled, in the order run, with its outcome.
+#[derive(Debug)]
+struct PolicyResult {
+    checks: Vec<(PolicyCheck, Result<bool, ZkitError>)>,
+}
+
+impl PolicyResult {
+    fn passed(&self) -> bool {
+        self.checks.iter().all(|(_, outcome)| matches!(outcome, Ok(true)))
+    }
+
+    fn failed(&self) -> Vec<PolicyCheck> {
+        self.checks
+            .iter()
+            .filter(|(_, outcome)| !matches!(outcome, Ok(true)))
+            .map(|(check, _)| *check)
+            .collect()
+    }
+}
+
+fn signed_proof_message(proof: &[u8]) -> Vec<u8> {
+    let mut message = SIGNED_PROOF_CONTEXT.to_vec();
+    message.extend_from_slice(proof);
+    message
+}
+
+// A proof after decode_stage, ready for verify_stage.
+#[derive(Clone, Debug)]
+struct DecodedProof {
+    transcript: Vec<u8>,
+    binding: TranscriptBinding,
+    // Public inputs already routed to instance columns
+    instances: Vec<Vec<Fp>>,
+    mode: VerifyMode,
+}
+
+// Where a verify_proof_timed_breakdown call spent its time. decode is decode_stage; verify is
+// verify_stage, the halo2 verifier dominated by its MSM; total also covers the glue around them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct VerifyTimings {
+    decode: Duration,
+    verify: Duration,
+    total: Duration,
+}
+
+// What verify_verbose actually checked a proof against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct VerifyOutcome {
+    ok: bool,
+    public_inputs: Vec<Fp>,
+    k: u32,
+    // Set when the proof's inputs differ from the expected ones; no crypto check was run then.
+    diff: Option<PublicInputDiff>,
+}
+
+// Index-wise differences between a proof's public inputs and the expected ones. (index, claimed,
+// expected); one side is None where the two lists have different lengths.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PublicInputDiff {
+    mismatches: Vec<(usize, Option<Fp>, Option<Fp>)>,
+}
+
+impl PublicInputDiff {
+    fn between(claimed: &[Fp], expected: &[Fp]) -> Option<Self> {
+        let mismatches: Vec<_> = (0..claimed.len().max(expected.len()))
+            .map(|i| (i, claimed.get(i).copied(), expected.get(i).copied()))
+            .filter(|(_, a, b)| a != b)
+            .collect();
+        if mismatches.is_empty() {
+            None
+        } else {
+            Some(Self { mismatches })
+        }
+    }
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s).map_err(serde::de::Error::custom)
+    }
+}
+
+// Field elements as hex of their canonical little-endian repr.
+mod fp_hex {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[Fp], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|x| hex::encode(x.to_repr())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Fp>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| {
+                let bytes = hex::decode(s).map_err(serde::de::Error::custom)?;
+                let mut repr = <Fp as PrimeField>::Repr::default();
+                if bytes.len() != repr.as_ref().len() {
+                    return Err(serde::de::Error::custom("field element must be 32 bytes"));
+                }
+                repr.as_mut().copy_from_slice(&bytes);
+                Option::from(Fp::from_repr(repr))
+                    .ok_or_else(|| serde::de::Error::custom("non-canonical field element"))
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+struct RecordExport {
+    id: u64,
+    encoding: Encoding,
+    orig_len: usize,
+    #[serde(with = "fp_hex")]
+    data: Vec<Fp>,
+    metadata: BTreeMap<String, String>,
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
 }
 
-impl CompressedData {
-    fn new(data: Vec<Fp>) -> Self {
-        Self { data }
+// Metadata key under which merge records the byte length of each part, comma separated.
+const MERGE_BOUNDARIES_KEY: &str = "zkit.merge.boundaries";
+
+// Per-proof values absorbed into the transcript, so the verifier must supply the same values for
+// the proof to check. The timestamp follows the domain tag; an external seed goes first of all.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct TranscriptBinding {
+    timestamp: Option<u64>,
+    // Never framed: both sides get it from whatever they interoperate with.
+    seed: Option<Vec<u8>>,
+}
+
+// Snapshot layout: magic | version (u8) | counter (u64) | record count (u64), then per record
+// id (u64) | checksum (32 bytes) | encoding (u8) | byte length (u64) | padding (u8) | element count
+// (u64) | encrypted (u8) | elements as 32-byte field reprs | metadata. Integers are LE. Version 1
+// has no encoding byte, versions 1-2 no byte length, versions 1-3 no metadata, versions 1-4 no
+// padding byte and versions 1-5 no encrypted byte. An encrypted record's checksum field is zeroed
+// and its elements are replaced by a sealed length (u64) | RecordCipher::seal of checksum and
+// element reprs.
+const SNAPSHOT_MAGIC: [u8; 4] = *b"ZKSS";
+const SNAPSHOT_VERSION: u8 = 6;
+
+// AES-256-GCM over a record's checksum and elements as snapshots and the WAL store them. Each
+// seal draws a fresh 96-bit nonce, stored ahead of the ciphertext, and binds the record id as
+// associated data so sealed records can't be swapped between ids. Ids, lengths, encoding,
+// padding and metadata stay in the clear.
+struct RecordCipher {
+    cipher: Aes256Gcm,
+}
+
+const RECORD_NONCE_LEN: usize = 12;
+
+impl RecordCipher {
+    fn new(key: &[u8; 32]) -> Self {
+        Self { cipher: Aes256Gcm::new(key.into()) }
+    }
+
+    fn seal(&self, id: u64, plaintext: &[u8]) -> Result<Vec<u8>, ZkitError> {
+        let mut nonce = [0u8; RECORD_NONCE_LEN];
+        OsRng.try_fill_bytes(&mut nonce).map_err(|_| ZkitError::EntropyUnavailable)?;
+        let aad = id.to_le_bytes();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| ZkitError::EntropyUnavailable)?;
+        let mut out = nonce.to_vec();
+        out.extend(ciphertext);
+        Ok(out)
+    }
+
+    fn open(&self, id: u64, sealed: &[u8]) -> Result<Vec<u8>, ZkitError> {
+        if sealed.len() < RECORD_NONCE_LEN {
+            return Err(ZkitError::DecryptionFailed);
+        }
+        let (nonce, ciphertext) = sealed.split_at(RECORD_NONCE_LEN);
+        let aad = id.to_le_bytes();
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| ZkitError::DecryptionFailed)
+    }
+}
+
+// Metadata follows a record's elements from v4 on: entry count, then length-prefixed UTF-8 key
+// and value for each entry, sorted by key so snapshots of equal stores are byte-identical.
+fn write_metadata(out: &mut impl Write, metadata: &HashMap<String, String>) -> io::Result<()> {
+    let mut keys: Vec<&String> = metadata.keys().collect();
+    keys.sort_unstable();
+    out.write_all(&(keys.len() as u64).to_le_bytes())?;
+    for key in keys {
+        for text in [key, &metadata[key]] {
+            out.write_all(&(text.len() as u64).to_le_bytes())?;
+            out.write_all(text.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_metadata(input: &mut impl Read) -> Result<HashMap<String, String>, ZkitError> {
+    let count = read_u64(input)?;
+    let mut metadata = HashMap::new();
+    for _ in 0..count {
+        let key = read_snapshot_string(input)?;
+        let value = read_snapshot_string(input)?;
+        metadata.insert(key, value);
+    }
+    Ok(metadata)
+}
+
+fn read_snapshot_string(input: &mut impl Read) -> Result<String, ZkitError> {
+    let len = read_u64(input)?;
+    let mut bytes = Vec::new();
+    if input.take(len).read_to_end(&mut bytes)? as u64 != len {
+        return Err(ZkitError::CorruptSnapshot);
+    }
+    String::from_utf8(bytes).map_err(|_| ZkitError::CorruptSnapshot)
+}
+
+// Returns (version, id counter, record count).
+fn read_snapshot_header(input: &mut impl Read) -> Result<(u8, u64, u64), ZkitError> {
+    let mut magic = [0u8; 4];
+    input.read_exact(&mut magic)?;
+    if magic != SNAPSHOT_MAGIC {
+        return Err(ZkitError::CorruptSnapshot);
+    }
+    let mut version = [0u8; 1];
+    input.read_exact(&mut version)?;
+    let version = version[0];
+    if version == 0 || version > SNAPSHOT_VERSION {
+        return Err(ZkitError::UnsupportedVersion(version));
+    }
+    let counter = read_u64(input)?;
+    let count = read_u64(input)?;
+    Ok((version, counter, count))
+}
+
+// Reads only the id and checksum of each record, skipping the element data. An encrypted record's
+// checksum is sealed with its elements and the field on disk is zeroed, so there is nothing to
+// compare without the key and those fail with EncryptedSnapshot.
+fn snapshot_checksums(path: &Path) -> Result<HashMap<u64, [u8; 32]>, ZkitError> {
+    let mut input = io::BufReader::new(File::open(path)?);
+    let (version, _, count) = read_snapshot_header(&mut input)?;
+    let mut checksums = HashMap::new();
+    for _ in 0..count {
+        let id = read_u64(&mut input)?;
+        let mut checksum = [0u8; 32];
+        input.read_exact(&mut checksum)?;
+        if version >= 2 {
+            input.read_exact(&mut [0u8; 1])?;
+        }
+        if version >= 3 {
+            read_u64(&mut input)?;
+        }
+        if version >= 5 {
+            input.read_exact(&mut [0u8; 1])?;
+        }
+        let len = read_u64(&mut input)?;
+        let mut encrypted = [0u8; 1];
+        if version >= 6 {
+            input.read_exact(&mut encrypted)?;
+        }
+        let skip = match encrypted[0] {
+            0 => len.checked_mul(32).ok_or(ZkitError::CorruptSnapshot)?,
+            1 => return Err(ZkitError::EncryptedSnapshot),
+            _ => return Err(ZkitError::CorruptSnapshot),
+        };
+        if io::copy(&mut (&mut input).take(skip), &mut io::sink())? != skip {
+            return Err(ZkitError::CorruptSnapshot);
+        }
+        if version >= 4 {
+            read_metadata(&mut input)?;
+        }
+        checksums.insert(id, checksum);
+    }
+    Ok(checksums)
+}
+
+fn write_snapshot_record(
+    out: &mut impl Write,
+    id: u64,
+    record: &CompressedData,
+    cipher: Option<&RecordCipher>,
+) -> Result<(), ZkitError> {
+    out.write_all(&id.to_le_bytes())?;
+    out.write_all(if cipher.is_some() { &[0u8; 32] } else { &record.checksum })?;
+    out.write_all(&[record.encoding.id()])?;
+    out.write_all(&(record.orig_len as u64).to_le_bytes())?;
+    out.write_all(&[record.padding.id()])?;
+    out.write_all(&(record.data.len() as u64).to_le_bytes())?;
+    out.write_all(&[cipher.is_some() as u8])?;
+    match cipher {
+        Some(cipher) => {
+            let mut plaintext = record.checksum.to_vec();
+            for x in &record.data {
+                plaintext.extend_from_slice(x.to_repr().as_ref());
+            }
+            let sealed = cipher.seal(id, &plaintext);
+            plaintext.zeroize();
+            let sealed = sealed?;
+            out.write_all(&(sealed.len() as u64).to_le_bytes())?;
+            out.write_all(&sealed)?;
+        }
+        None => {
+            for x in &record.data {
+                out.write_all(x.to_repr().as_ref())?;
+            }
+        }
+    }
+    Ok(write_metadata(out, &record.metadata)?)
+}
+
+// An encrypted record needs the cipher it was sealed with; without one, or with the wrong one,
+// this fails with DecryptionFailed.
+fn read_snapshot_record(
+    input: &mut impl Read,
+    version: u8,
+    cipher: Option<&RecordCipher>,
+) -> Result<(u64, CompressedData), ZkitError> {
+    let id = read_u64(input)?;
+    let mut checksum = [0u8; 32];
+    input.read_exact(&mut checksum)?;
+    let encoding = if version >= 2 {
+        let mut id = [0u8; 1];
+        input.read_exact(&mut id)?;
+        Encoding::from_id(id[0]).ok_or(ZkitError::CorruptSnapshot)?
+    } else {
+        Encoding::Bytewise
+    };
+    // A v2 record's byte length is recovered by legacy_orig_len, which only the built-in codecs
+    // without a base can do; custom codecs and Delta came later, so such a record is corrupt.
+    if version < 3 && matches!(encoding, Encoding::Custom(_) | Encoding::Delta) {
+        return Err(ZkitError::CorruptSnapshot);
+    }
+    let orig_len = if version >= 3 {
+        Some(read_u64(input)? as usize)
+    } else {
+        None
+    };
+    let padding = if version >= 5 {
+        let mut id = [0u8; 1];
+        input.read_exact(&mut id)?;
+        Padding::from_id(id[0]).ok_or(ZkitError::CorruptSnapshot)?
+    } else {
+        Padding::Zero
+    };
+    let len = read_u64(input)?;
+    let mut encrypted = [0u8; 1];
+    if version >= 6 {
+        input.read_exact(&mut encrypted)?;
+    }
+    let data = match encrypted[0] {
+        0 => (0..len).map(|_| read_fp(input)).collect::<Result<Vec<_>, _>>()?,
+        1 => {
+            let sealed_len = read_u64(input)?;
+            let mut sealed = Vec::new();
+            if input.take(sealed_len).read_to_end(&mut sealed)? as u64 != sealed_len {
+                return Err(ZkitError::CorruptSnapshot);
+            }
+            let mut plaintext = cipher.ok_or(ZkitError::DecryptionFailed)?.open(id, &sealed)?;
+            let expected = len.checked_mul(32).and_then(|n| n.checked_add(32)).ok_or(ZkitError::CorruptSnapshot)?;
+            if plaintext.len() as u64 != expected {
+                return Err(ZkitError::CorruptSnapshot);
+            }
+            checksum.copy_from_slice(&plaintext[..32]);
+            let mut elements = &plaintext[32..];
+            let data = (0..len).map(|_| read_fp(&mut elements)).collect::<Result<Vec<_>, _>>();
+            plaintext.zeroize();
+            data?
+        }
+        _ => return Err(ZkitError::CorruptSnapshot),
+    };
+    let orig_len = orig_len.unwrap_or_else(|| legacy_orig_len(encoding, &data));
+    let metadata = if version >= 4 {
+        read_metadata(input)?
+    } else {
+        HashMap::new()
+    };
+    Ok((id, CompressedData { data, checksum, encoding, orig_len, padding, metadata }))
+}
+
+// Returns (id, record, counter) for a put and (id, None, 0) for a delete.
+fn read_wal_entry(
+    input: &mut &[u8],
+    version: u8,
+    cipher: Option<&RecordCipher>,
+) -> Result<(u64, Option<CompressedData>, u64), ZkitError> {
+    let mut op = [0u8; 1];
+    input.read_exact(&mut op)?;
+    match op[0] {
+        WAL_PUT => {
+            let counter = read_u64(input)?;
+            let (id, record) = read_snapshot_record(input, version, cipher)?;
+            Ok((id, Some(record), counter))
+        }
+        WAL_DELETE => Ok((read_u64(input)?, None, 0)),
+        _ => Err(ZkitError::CorruptSnapshot),
+    }
+}
+
+// Byte export layout: magic | record count (u64), then per record id (u64) | byte length (u64) |
+// the decoded bytes. Integers are LE.
+const BYTES_EXPORT_MAGIC: [u8; 4] = *b"ZKBX";
+
+// Write-ahead log layout: magic | record format version (u8), then entries appended in the order
+// they were applied. A put is 0 (u8) | id counter after the write (u64) | the record as laid out
+// in a snapshot; a delete is 1 (u8) | id (u64). Integers are LE.
+const WAL_MAGIC: [u8; 4] = *b"ZKWL";
+const WAL_HEADER_LEN: u64 = 5;
+const WAL_PUT: u8 = 0;
+const WAL_DELETE: u8 = 1;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct SnapshotDiff {
+    added: Vec<u64>,
+    removed: Vec<u64>,
+    changed: Vec<u64>,
+}
+
+// Compares two snapshot files by record checksum; ids are reported in ascending order.
+fn diff_snapshots(a: &Path, b: &Path) -> Result<SnapshotDiff, ZkitError> {
+    let before = snapshot_checksums(a)?;
+    let after = snapshot_checksums(b)?;
+    let mut diff = SnapshotDiff::default();
+    for (id, checksum) in &after {
+        match before.get(id) {
+            None => diff.added.push(*id),
+            Some(old) if old != checksum => diff.changed.push(*id),
+            Some(_) => {}
+        }
+    }
+    diff.removed = before.keys().filter(|id| !after.contains_key(id)).copied().collect();
+    diff.added.sort_unstable();
+    diff.removed.sort_unstable();
+    diff.changed.sort_unstable();
+    Ok(diff)
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_fp(input: &mut impl Read) -> Result<Fp, ZkitError> {
+    let mut repr = <Fp as PrimeField>::Repr::default();
+    input.read_exact(repr.as_mut())?;
+    Option::from(Fp::from_repr(repr)).ok_or(ZkitError::CorruptSnapshot)
+}
+
+// Makes a newly created file's directory entry durable. Directories can only be opened this way
+// on Unix; elsewhere syncing the file itself is all we can do.
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    if cfg!(unix) {
+        let parent = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+const RETRY_ATTEMPTS: u32 = 4;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(10);
+
+// Runs `f` up to RETRY_ATTEMPTS times, doubling the pause after each transient failure.
+fn with_retry<T, E>(mut f: impl FnMut() -> Result<T, E>, transient: impl Fn(&E) -> bool) -> Result<T, E> {
+    let mut delay = RETRY_BASE_DELAY;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(e) if attempt < RETRY_ATTEMPTS && transient(&e) => {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn collect_files(dir: &Path, recursive: bool, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_file() {
+            out.push(entry.path());
+        } else if recursive && file_type.is_dir() {
+            collect_files(&entry.path(), true, out)?;
+        }
+    }
+    Ok(())
+}
+
+const MAX_INGEST_BYTES: usize = 4096;
+
+fn parse_ingest_bytes(line: &str) -> Result<Vec<u8>, ZkitError> {
+    let mut bytes = Vec::new();
+    for (position, token) in line.trim().split(',').enumerate() {
+        if position >= MAX_INGEST_BYTES {
+            return Err(ZkitError::InputTooLong { limit: MAX_INGEST_BYTES });
+        }
+        let token = token.trim();
+        let byte = token.parse::<u8>().map_err(|_| ZkitError::BadInput {
+            position,
+            token: token.to_string(),
+        })?;
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+fn parse_hex_bytes(line: &str) -> Result<Vec<u8>, ZkitError> {
+    let digits = line.trim();
+    let digits = digits.strip_prefix("0x").unwrap_or(digits);
+    if digits.len() / 2 > MAX_INGEST_BYTES {
+        return Err(ZkitError::InputTooLong { limit: MAX_INGEST_BYTES });
+    }
+    hex::decode(digits).map_err(|e| {
+        let position = match e {
+            hex::FromHexError::InvalidHexCharacter { index, .. } => index,
+            _ => digits.len(),
+        };
+        ZkitError::BadInput {
+            position,
+            token: digits.to_string(),
+        }
+    })
+}
+
+// A JSON array of integers 0..256. Invalid JSON is reported at the column serde_json stopped at;
+// a bad element at its index in the array.
+fn parse_json_bytes(line: &str) -> Result<Vec<u8>, ZkitError> {
+    let values: Vec<serde_json::Value> = serde_json::from_str(line).map_err(|e| ZkitError::BadInput {
+        position: e.column().saturating_sub(1),
+        token: line.to_string(),
+    })?;
+    if values.len() > MAX_INGEST_BYTES {
+        return Err(ZkitError::InputTooLong { limit: MAX_INGEST_BYTES });
+    }
+    values
+        .iter()
+        .enumerate()
+        .map(|(position, value)| {
+            value
+                .as_u64()
+                .and_then(|v| u8::try_from(v).ok())
+                .ok_or_else(|| ZkitError::BadInput {
+                    position,
+                    token: value.to_string(),
+                })
+        })
+        .collect()
+}
+
+// For pasted proofs: an optional 0x or 0X prefix, and whitespace or line breaks anywhere, are
+// ignored. BadHex positions count characters of the text as given; a dangling half-byte is
+// reported at the end of the input.
+fn parse_proof_hex(text: &str) -> Result<Vec<u8>, ZkitError> {
+    let trimmed = text.trim_start();
+    let mut offset = text.chars().count() - trimmed.chars().count();
+    let digits = match trimmed.strip_prefix("0x").or_else(|| trimmed.strip_prefix("0X")) {
+        Some(rest) => {
+            offset += 2;
+            rest
+        }
+        None => trimmed,
+    };
+    let mut bytes = Vec::with_capacity(digits.len() / 2);
+    let mut high = None;
+    for (idx, c) in digits.chars().enumerate() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let nibble = c.to_digit(16).ok_or(ZkitError::BadHex { position: offset + idx })? as u8;
+        match high.take() {
+            Some(h) => bytes.push(h << 4 | nibble),
+            None => high = Some(nibble),
+        }
+    }
+    if high.is_some() {
+        return Err(ZkitError::BadHex { position: text.chars().count() });
+    }
+    Ok(bytes)
+}
+
+// A leading [ selects a JSON array, a 0x prefix or any a-f digit selects hex; otherwise the line
+// is comma-separated decimal. Hex made only of decimal digits therefore needs the 0x prefix.
+fn parse_ingest_input(line: &str) -> Result<Vec<u8>, ZkitError> {
+    let line = line.trim();
+    if line.starts_with('[') {
+        return parse_json_bytes(line);
+    }
+    let is_hex = line.starts_with("0x")
+        || (!line.contains(',') && line.chars().any(|c| matches!(c, 'a'..='f' | 'A'..='F')));
+    if is_hex {
+        parse_hex_bytes(line)
+    } else {
+        parse_ingest_bytes(line)
+    }
+}
+
+// The id a Delta record's bytes are relative to, if any.
+fn delta_base(record: &CompressedData) -> Option<u64> {
+    if record.encoding != Encoding::Delta {
+        return None;
+    }
+    let repr = record.data.first()?.to_repr();
+    let id = u64::from_le_bytes(repr.as_ref()[..8].try_into().unwrap());
+    (id != 0).then_some(id)
+}
+
+// The padded byte stream of a Delta record on top of its base's decoded bytes.
+fn apply_deltas(base: &[u8], deltas: &[Fp], mode: DecodeMode) -> Result<Vec<u8>, ZkitError> {
+    deltas
+        .iter()
+        .enumerate()
+        .map(|(idx, &delta)| {
+            let value = Fp::from(base.get(idx).copied().unwrap_or(0) as u64) + delta;
+            match mode {
+                DecodeMode::Strict => fp_to_byte(&value),
+                DecodeMode::Lenient => Ok(fp_to_byte_lossy(&value)),
+            }
+        })
+        .collect()
+}
+
+fn ingest_and_compress(data: Vec<u8>, codec: &dyn Codec, padding: Padding) -> CompressedData {
+    let encoding = Encoding::from_id(codec.id()).expect("codec ids are validated on registration");
+    let data_fp: Vec<Fp> = codec.compress(&padding.apply(&data, element_bytes(encoding)));
+    let poly = Polynomial::from_vec(data_fp.clone());
+    let compressed_data = poly.to_vec();
+    let mut record = CompressedData::new(compressed_data, encoding, data.len());
+    record.padding = padding;
+    record
+}
+
+// INSTANCES is the number of instance columns; see route_public_inputs for how a flat
+// public_inputs slice is spread across them. LOOKUP adds the lookup argument and its table
+// column. It is part of the type because configure can't see the circuit, and a circuit without
+// it has to keep the constraint system, and so the vk and its fingerprint, it always had.
+#[derive(Clone)]
+struct ExampleCircuit<F: FieldExt, const INSTANCES: usize = 1, const LOOKUP: bool = false> {
+    pub data: Vec<F>,
+    // Allowed element values, only enforced when LOOKUP is set. The table is fixed data, so keys
+    // generated for one table only verify proofs made with the same table.
+    pub lookup: Option<Vec<F>>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt, const INSTANCES: usize> ExampleCircuit<F, INSTANCES> {
+    fn with_lookup(mut self, table: Vec<F>) -> ExampleCircuit<F, INSTANCES, true> {
+        ExampleCircuit {
+            data: std::mem::take(&mut self.data),
+            lookup: Some(table),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt, const INSTANCES: usize, const LOOKUP: bool> Circuit<F> for ExampleCircuit<F, INSTANCES, LOOKUP> {
+    type Config = ExampleConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            data: vec![],
+            lookup: self.lookup.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let input = meta.advice_column();
+        let public = (0..INSTANCES).map(|_| meta.instance_column()).collect();
+        let s = meta.selector();
+
+        meta.create_gate("data processing", |v_cells| {
+            let input_exp = v_cells.query_advice(input, Rotation::cur());
+            let s = v_cells.query_selector(s);
+
+            vec![s * input_exp]
+        });
+
+        let (table, s_lookup) = if LOOKUP {
+            let table = meta.lookup_table_column();
+            let s_lookup = meta.complex_selector();
+            // A disabled row looks up zero, which is why the table always carries a zero row.
+            meta.lookup(|v_cells| {
+                let input_exp = v_cells.query_advice(input, Rotation::cur());
+                let s = v_cells.query_selector(s_lookup);
+
+                vec![(s * input_exp, table)]
+            });
+            (Some(table), Some(s_lookup))
+        } else {
+            (None, None)
+        };
+
+        ExampleConfig {
+            input,
+            public,
+            s,
+            table,
+            s_lookup,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        if let Some(column) = config.table {
+            layouter.assign_table(
+                || "lookup",
+                |mut table| {
+                    let values = self.lookup.iter().flatten();
+                    for (row, &value) in std::iter::once(&F::zero()).chain(values).enumerate() {
+                        table.assign_cell(|| "allowed", column, row, || Value::known(value))?;
+                    }
+                    Ok(())
+                },
+            )?;
+        }
+
+        layouter.assign_region(
+            || "data processing",
+            |mut region| {
+                config.s.enable(&mut region, 0)?;
+
+                for (idx, &value) in self.data.iter().enumerate() {
+                    region.assign_advice(|| "input", config.input, idx, || Value::known(value))?;
+                    if let (Some(s_lookup), Some(_)) = (config.s_lookup, &self.lookup) {
+                        s_lookup.enable(&mut region, idx)?;
+                    }
+                }
+
+                Ok(())
+            },
+        )?;
+
+        Ok(())
+    }
+}
+
+impl<F: FieldExt, const INSTANCES: usize, const LOOKUP: bool> Zeroize for ExampleCircuit<F, INSTANCES, LOOKUP> {
+    fn zeroize(&mut self) {
+        zeroize_elements(&mut self.data);
+    }
+}
+
+// The witness is held by the circuit for the duration of proving, so wipe it when the circuit goes away.
+impl<F: FieldExt, const INSTANCES: usize, const LOOKUP: bool> Drop for ExampleCircuit<F, INSTANCES, LOOKUP> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+// Spreads a flat public_inputs slice across the instance columns round-robin: input i goes to
+// column i % columns, row i / columns. With a single column this is the slice itself.
+fn route_public_inputs(public_inputs: &[Fp], columns: usize) -> Vec<Vec<Fp>> {
+    let mut routed = vec![Vec::new(); columns.max(1)];
+    for (idx, &value) in public_inputs.iter().enumerate() {
+        routed[idx % routed.len()].push(value);
+    }
+    routed
+}
+
+#[derive(Clone, Debug)]
+struct ExampleConfig {
+    input: Column<Advice>,
+    // Public inputs are committed through the transcript; the example gate doesn't read them.
+    public: Vec<Column<Instance>>,
+    s: Selector,
+    table: Option<TableColumn>,
+    s_lookup: Option<Selector>,
+}
+
+// Proves two equal-length records are element-wise identical. Each is bound to its stored
+// contents through record_hash_gadget. Instance rows: id_a, id_b, hash_a, hash_b.
+struct EqualityCircuit {
+    a: Vec<Value<Fp>>,
+    b: Vec<Value<Fp>>,
+}
+
+#[derive(Clone, Debug)]
+struct EqualityConfig {
+    hash: HashConfig,
+    a: Column<Advice>,
+    b: Column<Advice>,
+    s: Selector,
+}
+
+impl Circuit<Fp> for EqualityCircuit {
+    type Config = EqualityConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: vec![Value::unknown(); self.a.len()],
+            b: vec![Value::unknown(); self.b.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let a = meta.advice_column();
+        let b = meta.advice_column();
+        let s = meta.selector();
+        meta.enable_equality(a);
+        meta.enable_equality(b);
+
+        meta.create_gate("equality", |v_cells| {
+            let a = v_cells.query_advice(a, Rotation::cur());
+            let b = v_cells.query_advice(b, Rotation::cur());
+            let s = v_cells.query_selector(s);
+
+            vec![s * (a - b)]
+        });
+
+        EqualityConfig { hash, a, b, s }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let (digest_a, cells_a) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.a,
+            layouter.namespace(|| "hash a"),
+        )?;
+        layouter.constrain_instance(digest_a.cell(), config.hash.expected, 2)?;
+        let (digest_b, cells_b) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.b,
+            layouter.namespace(|| "hash b"),
+        )?;
+        layouter.constrain_instance(digest_b.cell(), config.hash.expected, 3)?;
+
+        layouter.assign_region(
+            || "equality",
+            |mut region| {
+                for (idx, (a, b)) in cells_a.iter().zip(&cells_b).enumerate() {
+                    config.s.enable(&mut region, idx)?;
+                    a.copy_advice(|| "a", &mut region, config.a, idx)?;
+                    b.copy_advice(|| "b", &mut region, config.b, idx)?;
+                }
+
+                Ok(())
+            },
+        )
+    }
+}
+
+// Running-sum accumulator whose final row is constrained to the public claimed sum. The values
+// are loaded through record_hash_gadget, whose digest is the first instance column's only row.
+struct SumCircuit {
+    values: Vec<Value<Fp>>,
+}
+
+#[derive(Clone, Debug)]
+struct SumConfig {
+    value: Column<Advice>,
+    acc: Column<Advice>,
+    sum: Column<Instance>,
+    s_first: Selector,
+    s_step: Selector,
+}
+
+impl SumConfig {
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self {
+        let value = meta.advice_column();
+        let acc = meta.advice_column();
+        let sum = meta.instance_column();
+        let s_first = meta.selector();
+        let s_step = meta.selector();
+        meta.enable_equality(value);
+        meta.enable_equality(acc);
+        meta.enable_equality(sum);
+
+        meta.create_gate("sum first", |v_cells| {
+            let value = v_cells.query_advice(value, Rotation::cur());
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let s = v_cells.query_selector(s_first);
+
+            vec![s * (acc - value)]
+        });
+
+        meta.create_gate("sum step", |v_cells| {
+            let value = v_cells.query_advice(value, Rotation::cur());
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let prev = v_cells.query_advice(acc, Rotation::prev());
+            let s = v_cells.query_selector(s_step);
+
+            vec![s * (acc - prev - value)]
+        });
+
+        Self { value, acc, sum, s_first, s_step }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct RecordSumConfig {
+    hash: HashConfig,
+    sum: SumConfig,
+}
+
+impl Circuit<Fp> for SumCircuit {
+    type Config = RecordSumConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        RecordSumConfig {
+            hash: HashCircuit::configure(meta),
+            sum: SumConfig::configure(meta),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.hash.expected, 0)?;
+
+        let config = config.sum;
+        let total = layouter.assign_region(
+            || "sum",
+            |mut region| {
+                let mut acc = Value::known(Fp::zero());
+                let mut last = None;
+                for (idx, cell) in cells.iter().enumerate() {
+                    if idx == 0 {
+                        config.s_first.enable(&mut region, idx)?;
+                    } else {
+                        config.s_step.enable(&mut region, idx)?;
+                    }
+                    cell.copy_advice(|| "value", &mut region, config.value, idx)?;
+                    acc = acc + cell.value().copied();
+                    last = Some(region.assign_advice(|| "acc", config.acc, idx, || acc)?);
+                }
+
+                Ok(last)
+            },
+        )?;
+
+        if let Some(total) = total {
+            layouter.constrain_instance(total.cell(), config.sum, 0)?;
+        }
+        Ok(())
+    }
+}
+
+fn poseidon2(a: Fp, b: Fp) -> Fp {
+    poseidon::Hash::<_, P128Pow5T3, ConstantLength<2>, 3, 2>::init().hash([a, b])
+}
+
+// h_0 = len, h_i = Poseidon(h_{i-1}, x_i). Seeding with the length binds it into the hash.
+fn record_hash(data: &[Fp]) -> Fp {
+    data.iter().fold(Fp::from(data.len() as u64), |acc, &x| poseidon2(acc, x))
+}
+
+fn configure_poseidon(meta: &mut ConstraintSystem<Fp>) -> Pow5Config<Fp, 3, 2> {
+    let state = [meta.advice_column(), meta.advice_column(), meta.advice_column()];
+    let partial_sbox = meta.advice_column();
+    let rc_a = [meta.fixed_column(), meta.fixed_column(), meta.fixed_column()];
+    let rc_b = [meta.fixed_column(), meta.fixed_column(), meta.fixed_column()];
+    meta.enable_constant(rc_b[0]);
+    Pow5Chip::configure::<P128Pow5T3>(meta, state, partial_sbox, rc_a, rc_b)
+}
+
+// In-circuit counterpart of record_hash over cells loaded into `input`. The element cells are
+// returned alongside the digest so callers can bind individual elements to other constraints.
+fn record_hash_gadget(
+    poseidon: &Pow5Config<Fp, 3, 2>,
+    input: Column<Advice>,
+    values: &[Value<Fp>],
+    mut layouter: impl Layouter<Fp>,
+) -> Result<(AssignedCell<Fp, Fp>, Vec<AssignedCell<Fp, Fp>>), Error> {
+    let (mut acc, cells) = layouter.assign_region(
+        || "load record",
+        |mut region| {
+            let acc = region.assign_advice_from_constant(|| "length", input, 0, Fp::from(values.len() as u64))?;
+            let cells = values
+                .iter()
+                .enumerate()
+                .map(|(idx, &value)| region.assign_advice(|| "element", input, idx + 1, || value))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((acc, cells))
+        },
+    )?;
+
+    for (idx, cell) in cells.iter().enumerate() {
+        let hasher = PoseidonHash::<_, _, P128Pow5T3, ConstantLength<2>, 3, 2>::init(
+            Pow5Chip::construct(poseidon.clone()),
+            layouter.namespace(|| format!("init {}", idx)),
+        )?;
+        acc = hasher.hash(layouter.namespace(|| format!("absorb {}", idx)), [acc, cell.clone()])?;
+    }
+    Ok((acc, cells))
+}
+
+// Proves the witnessed record hashes (via record_hash) to the public expected value.
+struct HashCircuit {
+    values: Vec<Value<Fp>>,
+}
+
+#[derive(Clone, Debug)]
+struct HashConfig {
+    input: Column<Advice>,
+    expected: Column<Instance>,
+    poseidon: Pow5Config<Fp, 3, 2>,
+}
+
+impl Circuit<Fp> for HashCircuit {
+    type Config = HashConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let input = meta.advice_column();
+        let expected = meta.instance_column();
+        meta.enable_equality(input);
+        meta.enable_equality(expected);
+
+        HashConfig {
+            input,
+            expected,
+            poseidon: configure_poseidon(meta),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let (digest, _) = record_hash_gadget(
+            &config.poseidon,
+            config.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.expected, 0)
+    }
+}
+
+// Proves the listed elements of a record hashing to instance row 0 equal instance rows 1..,
+// in the order given; the remaining elements stay witnessed.
+struct DisclosureCircuit {
+    values: Vec<Value<Fp>>,
+    disclosed: Vec<usize>,
+}
+
+impl Circuit<Fp> for DisclosureCircuit {
+    type Config = HashConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+            disclosed: self.disclosed.clone(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        HashCircuit::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let (digest, cells) = record_hash_gadget(
+            &config.poseidon,
+            config.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.expected, 0)?;
+        for (row, &idx) in self.disclosed.iter().enumerate() {
+            layouter.constrain_instance(cells[idx].cell(), config.expected, row + 1)?;
+        }
+        Ok(())
+    }
+}
+
+// Each adjacent gap x_{i+1} - x_i is decomposed into this many byte limbs, so the circuit accepts
+// a step of at most 2^64 - 1; a decreasing step wraps to a huge field element and cannot decompose.
+const SORT_GAP_LIMBS: usize = 8;
+
+// Gap below 2^(8 * SORT_GAP_LIMBS), checked the same way the circuit does.
+fn gap_in_range(prev: Fp, next: Fp) -> bool {
+    (next - prev).to_repr().as_ref()[SORT_GAP_LIMBS..].iter().all(|&b| b == 0)
+}
+
+// Proves the record hashing to the public value is non-decreasing. Every gap is rebuilt from
+// byte limbs (big-endian running sum), each limb looked up in a 0..256 table.
+struct SortedCircuit {
+    values: Vec<Value<Fp>>,
+}
+
+#[derive(Clone, Debug)]
+struct SortedConfig {
+    hash: HashConfig,
+    prev: Column<Advice>,
+    next: Column<Advice>,
+    limb: Column<Advice>,
+    acc: Column<Advice>,
+    bytes: TableColumn,
+    s_limb: Selector,
+    s_first: Selector,
+    s_step: Selector,
+    s_gap: Selector,
+}
+
+impl Circuit<Fp> for SortedCircuit {
+    type Config = SortedConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let prev = meta.advice_column();
+        let next = meta.advice_column();
+        let limb = meta.advice_column();
+        let acc = meta.advice_column();
+        let bytes = meta.lookup_table_column();
+        let s_limb = meta.complex_selector();
+        let s_first = meta.selector();
+        let s_step = meta.selector();
+        let s_gap = meta.selector();
+        meta.enable_equality(prev);
+        meta.enable_equality(next);
+
+        meta.lookup(|v_cells| {
+            let limb = v_cells.query_advice(limb, Rotation::cur());
+            let s = v_cells.query_selector(s_limb);
+
+            vec![(s * limb, bytes)]
+        });
+
+        meta.create_gate("gap first", |v_cells| {
+            let limb = v_cells.query_advice(limb, Rotation::cur());
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let s = v_cells.query_selector(s_first);
+
+            vec![s * (acc - limb)]
+        });
+
+        meta.create_gate("gap step", |v_cells| {
+            let limb = v_cells.query_advice(limb, Rotation::cur());
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let last = v_cells.query_advice(acc, Rotation::prev());
+            let s = v_cells.query_selector(s_step);
+
+            vec![s * (acc - last * Fp::from(256) - limb)]
+        });
+
+        meta.create_gate("gap total", |v_cells| {
+            let prev = v_cells.query_advice(prev, Rotation::cur());
+            let next = v_cells.query_advice(next, Rotation::cur());
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let s = v_cells.query_selector(s_gap);
+
+            vec![s * (acc - (next - prev))]
+        });
+
+        SortedConfig { hash, prev, next, limb, acc, bytes, s_limb, s_first, s_step, s_gap }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        layouter.assign_table(
+            || "bytes",
+            |mut table| {
+                for byte in 0..256u64 {
+                    table.assign_cell(|| "byte", config.bytes, byte as usize, || Value::known(Fp::from(byte)))?;
+                }
+                Ok(())
+            },
+        )?;
+
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.hash.expected, 0)?;
+
+        for (idx, pair) in cells.windows(2).enumerate() {
+            assign_gap(&config, layouter.namespace(|| format!("gap {}", idx)), &pair[0], &pair[1])?;
+        }
+        Ok(())
+    }
+}
+
+// Constrains next - prev to decompose into SORT_GAP_LIMBS byte limbs, i.e. prev <= next with a
+// gap below 2^64.
+fn assign_gap(
+    config: &SortedConfig,
+    mut layouter: impl Layouter<Fp>,
+    prev: &AssignedCell<Fp, Fp>,
+    next: &AssignedCell<Fp, Fp>,
+) -> Result<(), Error> {
+    layouter.assign_region(
+        || "gap",
+        |mut region| {
+            let gap = next.value().copied() - prev.value().copied();
+            let mut acc = Value::known(Fp::zero());
+            for row in 0..SORT_GAP_LIMBS {
+                config.s_limb.enable(&mut region, row)?;
+                if row == 0 {
+                    config.s_first.enable(&mut region, row)?;
+                } else {
+                    config.s_step.enable(&mut region, row)?;
+                }
+                let limb = gap.map(|g| Fp::from(g.to_repr().as_ref()[SORT_GAP_LIMBS - 1 - row] as u64));
+                region.assign_advice(|| "limb", config.limb, row, || limb)?;
+                acc = acc * Value::known(Fp::from(256)) + limb;
+                region.assign_advice(|| "acc", config.acc, row, || acc)?;
+            }
+            let last = SORT_GAP_LIMBS - 1;
+            config.s_gap.enable(&mut region, last)?;
+            prev.copy_advice(|| "prev", &mut region, config.prev, last)?;
+            next.copy_advice(|| "next", &mut region, config.next, last)?;
+            Ok(())
+        },
+    )
+}
+
+// Proves every element x of the record hashing to instance row 0 satisfies lo <= x <= hi for lo
+// and hi in rows 1 and 2, as two gap checks per element. Gaps are limited to 2^64 like SortedCircuit's, so a range
+// wider than that is enforced as the narrower window within 2^64 of both ends.
+struct BoundedCircuit {
+    values: Vec<Value<Fp>>,
+}
+
+impl Circuit<Fp> for BoundedCircuit {
+    type Config = SortedConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        SortedCircuit::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        layouter.assign_table(
+            || "bytes",
+            |mut table| {
+                for byte in 0..256u64 {
+                    table.assign_cell(|| "byte", config.bytes, byte as usize, || Value::known(Fp::from(byte)))?;
+                }
+                Ok(())
+            },
+        )?;
+
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.hash.expected, 0)?;
+
+        let input = config.hash.input;
+        let (lo, hi) = layouter.assign_region(
+            || "load bounds",
+            |mut region| {
+                let lo = region.assign_advice_from_instance(|| "lo", config.hash.expected, 1, input, 0)?;
+                let hi = region.assign_advice_from_instance(|| "hi", config.hash.expected, 2, input, 1)?;
+                Ok((lo, hi))
+            },
+        )?;
+
+        for (idx, cell) in cells.iter().enumerate() {
+            assign_gap(&config, layouter.namespace(|| format!("low {}", idx)), &lo, cell)?;
+            assign_gap(&config, layouter.namespace(|| format!("high {}", idx)), cell, &hi)?;
+        }
+        Ok(())
+    }
+}
+
+// Proves that exactly public[1] of the elements of the record hashing to the first instance column's
+// only row equal public[0]. Each row carries an is-zero indicator for (value - target), with the
+// inverse as a hint, and a running sum of indicators.
+struct CountCircuit {
+    values: Vec<Value<Fp>>,
+}
+
+#[derive(Clone, Debug)]
+struct CountConfig {
+    hash: HashConfig,
+    value: Column<Advice>,
+    target: Column<Advice>,
+    inv: Column<Advice>,
+    hit: Column<Advice>,
+    acc: Column<Advice>,
+    public: Column<Instance>,
+    s: Selector,
+    s_first: Selector,
+    s_step: Selector,
+}
+
+impl Circuit<Fp> for CountCircuit {
+    type Config = CountConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let value = meta.advice_column();
+        let target = meta.advice_column();
+        let inv = meta.advice_column();
+        let hit = meta.advice_column();
+        let acc = meta.advice_column();
+        let public = meta.instance_column();
+        let s = meta.selector();
+        let s_first = meta.selector();
+        let s_step = meta.selector();
+        meta.enable_equality(value);
+        meta.enable_equality(target);
+        meta.enable_equality(acc);
+        meta.enable_equality(public);
+
+        meta.create_gate("count indicator", |v_cells| {
+            let value = v_cells.query_advice(value, Rotation::cur());
+            let target = v_cells.query_advice(target, Rotation::cur());
+            let inv = v_cells.query_advice(inv, Rotation::cur());
+            let hit = v_cells.query_advice(hit, Rotation::cur());
+            let s = v_cells.query_selector(s);
+            let diff = value - target;
+
+            vec![
+                s.clone() * (hit.clone() - (Expression::Constant(Fp::one()) - diff.clone() * inv)),
+                s * diff * hit,
+            ]
+        });
+
+        meta.create_gate("count first", |v_cells| {
+            let hit = v_cells.query_advice(hit, Rotation::cur());
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let s = v_cells.query_selector(s_first);
+
+            vec![s * (acc - hit)]
+        });
+
+        meta.create_gate("count step", |v_cells| {
+            let hit = v_cells.query_advice(hit, Rotation::cur());
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let prev = v_cells.query_advice(acc, Rotation::prev());
+            let s = v_cells.query_selector(s_step);
+
+            vec![s * (acc - prev - hit)]
+        });
+
+        CountConfig { hash, value, target, inv, hit, acc, public, s, s_first, s_step }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.hash.expected, 0)?;
+
+        let total = layouter.assign_region(
+            || "count",
+            |mut region| {
+                let mut acc = Value::known(Fp::zero());
+                let mut last = None;
+                for (idx, cell) in cells.iter().enumerate() {
+                    config.s.enable(&mut region, idx)?;
+                    if idx == 0 {
+                        config.s_first.enable(&mut region, idx)?;
+                    } else {
+                        config.s_step.enable(&mut region, idx)?;
+                    }
+                    let target =
+                        region.assign_advice_from_instance(|| "target", config.public, 0, config.target, idx)?;
+                    let value = cell.copy_advice(|| "value", &mut region, config.value, idx)?;
+                    let diff = value.value().copied() - target.value().copied();
+                    let inv = diff.map(|d| d.invert().unwrap_or(Fp::zero()));
+                    let hit = diff.map(|d| if d == Fp::zero() { Fp::one() } else { Fp::zero() });
+                    region.assign_advice(|| "inv", config.inv, idx, || inv)?;
+                    region.assign_advice(|| "hit", config.hit, idx, || hit)?;
+                    acc = acc + hit;
+                    last = Some(region.assign_advice(|| "acc", config.acc, idx, || acc)?);
+                }
+
+                Ok(last)
+            },
+        )?;
+
+        if let Some(total) = total {
+            layouter.constrain_instance(total.cell(), config.public, 1)?;
+        }
+        Ok(())
+    }
+}
+
+// Proves the bytes of the record hashing to the first instance column's only row have public[0]
+// set bits in total. Each row splits its byte into
+// eight boolean bit cells that recompose to it, so a non-byte element has no valid witness, and
+// acc runs over the per-row bit sums.
+struct PopcountCircuit {
+    values: Vec<Value<Fp>>,
+}
+
+#[derive(Clone, Debug)]
+struct PopcountConfig {
+    hash: HashConfig,
+    value: Column<Advice>,
+    bits: [Column<Advice>; 8],
+    acc: Column<Advice>,
+    public: Column<Instance>,
+    s: Selector,
+    s_first: Selector,
+    s_step: Selector,
+}
+
+impl Circuit<Fp> for PopcountCircuit {
+    type Config = PopcountConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let value = meta.advice_column();
+        let bits = [(); 8].map(|_| meta.advice_column());
+        let acc = meta.advice_column();
+        let public = meta.instance_column();
+        let s = meta.selector();
+        let s_first = meta.selector();
+        let s_step = meta.selector();
+        meta.enable_equality(value);
+        meta.enable_equality(acc);
+        meta.enable_equality(public);
+
+        meta.create_gate("popcount bits", |v_cells| {
+            let s = v_cells.query_selector(s);
+            let value = v_cells.query_advice(value, Rotation::cur());
+            let bits: Vec<_> = bits.iter().map(|&bit| v_cells.query_advice(bit, Rotation::cur())).collect();
+            let one = Expression::Constant(Fp::one());
+
+            let mut constraints: Vec<_> = bits
+                .iter()
+                .map(|bit| s.clone() * bit.clone() * (one.clone() - bit.clone()))
+                .collect();
+            let recomposed = bits
+                .iter()
+                .enumerate()
+                .fold(Expression::Constant(Fp::zero()), |sum, (i, bit)| {
+                    sum + bit.clone() * Expression::Constant(Fp::from(1u64 << i))
+                });
+            constraints.push(s * (value - recomposed));
+            constraints
+        });
+
+        let weight = |v_cells: &mut VirtualCells<'_, Fp>| {
+            bits.iter()
+                .fold(Expression::Constant(Fp::zero()), |sum, &bit| sum + v_cells.query_advice(bit, Rotation::cur()))
+        };
+
+        meta.create_gate("popcount first", |v_cells| {
+            let s = v_cells.query_selector(s_first);
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+
+            vec![s * (acc - weight(v_cells))]
+        });
+
+        meta.create_gate("popcount step", |v_cells| {
+            let s = v_cells.query_selector(s_step);
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let prev = v_cells.query_advice(acc, Rotation::prev());
+
+            vec![s * (acc - prev - weight(v_cells))]
+        });
+
+        PopcountConfig { hash, value, bits, acc, public, s, s_first, s_step }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.hash.expected, 0)?;
+
+        let total = layouter.assign_region(
+            || "popcount",
+            |mut region| {
+                let mut acc = Value::known(Fp::zero());
+                let mut last = None;
+                for (idx, cell) in cells.iter().enumerate() {
+                    config.s.enable(&mut region, idx)?;
+                    if idx == 0 {
+                        config.s_first.enable(&mut region, idx)?;
+                    } else {
+                        config.s_step.enable(&mut region, idx)?;
+                    }
+                    let value = cell.copy_advice(|| "value", &mut region, config.value, idx)?;
+                    let byte = value.value().map(fp_to_byte_lossy);
+                    for (i, &column) in config.bits.iter().enumerate() {
+                        let bit = byte.map(|b| Fp::from(((b >> i) & 1) as u64));
+                        region.assign_advice(|| "bit", column, idx, || bit)?;
+                    }
+                    acc = acc + byte.map(|b| Fp::from(b.count_ones() as u64));
+                    last = Some(region.assign_advice(|| "acc", config.acc, idx, || acc)?);
+                }
+
+                Ok(last)
+            },
+        )?;
+
+        if let Some(total) = total {
+            layouter.constrain_instance(total.cell(), config.public, 0)?;
+        }
+        Ok(())
+    }
+}
+
+// UTF-8 validator (RFC 3629: no overlongs, surrogates or code points above U+10FFFF) as a DFA.
+// State 0 is "between characters", 1-3 expect that many continuation bytes, and 4-7 are the
+// restricted second bytes after E0, ED, F0 and F4. None means the byte is invalid in that state.
+const UTF8_STATES: u8 = 8;
+
+fn utf8_step(state: u8, byte: u8) -> Option<u8> {
+    match (state, byte) {
+        (0, 0x00..=0x7f) => Some(0),
+        (0, 0xc2..=0xdf) => Some(1),
+        (0, 0xe0) => Some(4),
+        (0, 0xe1..=0xec) | (0, 0xee..=0xef) => Some(2),
+        (0, 0xed) => Some(5),
+        (0, 0xf0) => Some(6),
+        (0, 0xf1..=0xf3) => Some(3),
+        (0, 0xf4) => Some(7),
+        (1, 0x80..=0xbf) => Some(0),
+        (2, 0x80..=0xbf) => Some(1),
+        (3, 0x80..=0xbf) => Some(2),
+        (4, 0xa0..=0xbf) => Some(1),
+        (5, 0x80..=0x9f) => Some(1),
+        (6, 0x90..=0xbf) => Some(2),
+        (7, 0x80..=0x8f) => Some(2),
+        _ => None,
+    }
+}
+
+// Proves a Bytewise record hashing to the public value is valid UTF-8. Each byte and the DFA
+// state around it are looked up as a (state, byte, next state) triple in the transition table;
+// the walk starts and ends in state 0.
+struct Utf8Circuit {
+    values: Vec<Value<Fp>>,
+}
+
+#[derive(Clone, Debug)]
+struct Utf8Config {
+    hash: HashConfig,
+    byte: Column<Advice>,
+    state: Column<Advice>,
+    table: [TableColumn; 3],
+    s_step: Selector,
+    s_accept: Selector,
+}
+
+impl Circuit<Fp> for Utf8Circuit {
+    type Config = Utf8Config;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let byte = meta.advice_column();
+        let state = meta.advice_column();
+        let table = [meta.lookup_table_column(), meta.lookup_table_column(), meta.lookup_table_column()];
+        let s_step = meta.complex_selector();
+        let s_accept = meta.selector();
+        meta.enable_equality(byte);
+
+        meta.lookup(|v_cells| {
+            let state_cur = v_cells.query_advice(state, Rotation::cur());
+            let byte = v_cells.query_advice(byte, Rotation::cur());
+            let state_next = v_cells.query_advice(state, Rotation::next());
+            let s = v_cells.query_selector(s_step);
+
+            vec![
+                (s.clone() * state_cur, table[0]),
+                (s.clone() * byte, table[1]),
+                (s * state_next, table[2]),
+            ]
+        });
+
+        meta.create_gate("utf8 boundary", |v_cells| {
+            let state = v_cells.query_advice(state, Rotation::cur());
+            let s = v_cells.query_selector(s_accept);
+
+            vec![s * state]
+        });
+
+        Utf8Config { hash, byte, state, table, s_step, s_accept }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        layouter.assign_table(
+            || "utf8 transitions",
+            |mut table| {
+                let mut row = 0;
+                for state in 0..UTF8_STATES {
+                    for byte in 0..=255u8 {
+                        if let Some(next) = utf8_step(state, byte) {
+                            for (column, value) in config.table.iter().zip([state, byte, next]) {
+                                table.assign_cell(|| "transition", *column, row, || Value::known(Fp::from(value as u64)))?;
+                            }
+                            row += 1;
+                        }
+                    }
+                }
+                Ok(())
+            },
+        )?;
+
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.hash.expected, 0)?;
+
+        layouter.assign_region(
+            || "utf8",
+            |mut region| {
+                // An invalid byte has no transition; its state is left at 0 and the lookup fails.
+                let mut state = Value::known(0u8);
+                config.s_accept.enable(&mut region, 0)?;
+                for (idx, cell) in cells.iter().enumerate() {
+                    config.s_step.enable(&mut region, idx)?;
+                    cell.copy_advice(|| "byte", &mut region, config.byte, idx)?;
+                    region.assign_advice(|| "state", config.state, idx, || state.map(|s| Fp::from(s as u64)))?;
+                    state = state.zip(cell.value().copied()).map(|(s, byte)| {
+                        fp_to_byte(&byte).ok().and_then(|byte| utf8_step(s, byte)).unwrap_or(0)
+                    });
+                }
+                config.s_accept.enable(&mut region, cells.len())?;
+                region.assign_advice(|| "state", config.state, cells.len(), || state.map(|s| Fp::from(s as u64)))?;
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
+}
+
+// Proves the XOR of a bytewise record's bytes equals instance row 1. Each row splits one byte into
+// bits and folds them into a running XOR held as bits, acc' = acc + bit - 2 * acc * bit; the row
+// after the last byte recomposes the accumulator.
+struct XorCircuit {
+    values: Vec<Value<Fp>>,
+}
+
+#[derive(Clone, Debug)]
+struct XorConfig {
+    hash: HashConfig,
+    byte: Column<Advice>,
+    bits: [Column<Advice>; 8],
+    acc: [Column<Advice>; 8],
+    out: Column<Advice>,
+    s_first: Selector,
+    s_step: Selector,
+    s_out: Selector,
+}
+
+impl Circuit<Fp> for XorCircuit {
+    type Config = XorConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let byte = meta.advice_column();
+        let bits = [(); 8].map(|_| meta.advice_column());
+        let acc = [(); 8].map(|_| meta.advice_column());
+        let out = meta.advice_column();
+        let s_first = meta.selector();
+        let s_step = meta.selector();
+        let s_out = meta.selector();
+        meta.enable_equality(byte);
+        meta.enable_equality(out);
+
+        meta.create_gate("xor start", |v_cells| {
+            let s = v_cells.query_selector(s_first);
+            acc.iter()
+                .map(|&column| s.clone() * v_cells.query_advice(column, Rotation::cur()))
+                .collect::<Vec<_>>()
+        });
+
+        meta.create_gate("xor step", |v_cells| {
+            let s = v_cells.query_selector(s_step);
+            let one = Expression::Constant(Fp::one());
+            let two = Expression::Constant(Fp::from(2));
+            let mut constraints = Vec::new();
+            let mut composed = Expression::Constant(Fp::zero());
+            for j in 0..8 {
+                let bit = v_cells.query_advice(bits[j], Rotation::cur());
+                let cur = v_cells.query_advice(acc[j], Rotation::cur());
+                let next = v_cells.query_advice(acc[j], Rotation::next());
+                constraints.push(s.clone() * bit.clone() * (one.clone() - bit.clone()));
+                constraints.push(
+                    s.clone() * (next - (cur.clone() + bit.clone() - two.clone() * cur * bit.clone())),
+                );
+                composed = composed + bit * Expression::Constant(Fp::from(1 << j));
+            }
+            let byte = v_cells.query_advice(byte, Rotation::cur());
+            constraints.push(s * (byte - composed));
+            constraints
+        });
+
+        meta.create_gate("xor result", |v_cells| {
+            let s = v_cells.query_selector(s_out);
+            let out = v_cells.query_advice(out, Rotation::cur());
+            let composed = (0..8).fold(Expression::Constant(Fp::zero()), |sum, j| {
+                sum + v_cells.query_advice(acc[j], Rotation::cur()) * Expression::Constant(Fp::from(1 << j))
+            });
+
+            vec![s * (out - composed)]
+        });
+
+        XorConfig { hash, byte, bits, acc, out, s_first, s_step, s_out }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.hash.expected, 0)?;
+
+        layouter.assign_region(
+            || "xor fold",
+            |mut region| {
+                config.s_first.enable(&mut region, 0)?;
+                let mut acc = Value::known(0u8);
+                for (idx, cell) in cells.iter().enumerate() {
+                    config.s_step.enable(&mut region, idx)?;
+                    cell.copy_advice(|| "byte", &mut region, config.byte, idx)?;
+                    // A non-byte element leaves no valid decomposition, so the recomposition fails.
+                    let byte = cell.value().map(fp_to_byte_lossy);
+                    for j in 0..8 {
+                        region.assign_advice(|| "bit", config.bits[j], idx, || byte.map(|b| Fp::from(((b >> j) & 1) as u64)))?;
+                        region.assign_advice(|| "acc", config.acc[j], idx, || acc.map(|a| Fp::from(((a >> j) & 1) as u64)))?;
+                    }
+                    acc = acc.zip(byte).map(|(a, b)| a ^ b);
+                }
+                let last = cells.len();
+                config.s_out.enable(&mut region, last)?;
+                for j in 0..8 {
+                    region.assign_advice(|| "acc", config.acc[j], last, || acc.map(|a| Fp::from(((a >> j) & 1) as u64)))?;
+                }
+                region.assign_advice_from_instance(|| "claimed", config.hash.expected, 1, config.out, last)?;
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
+}
+
+// Public predicates prove_exists_matching can show some stored record satisfies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PredicateParams {
+    // Some element of the record equals this value
+    Contains(Fp),
+}
+
+impl PredicateParams {
+    fn holds(&self, data: &[Fp]) -> bool {
+        match self {
+            PredicateParams::Contains(value) => data.contains(value),
+        }
+    }
+
+    fn value(&self) -> Fp {
+        match self {
+            PredicateParams::Contains(value) => *value,
+        }
+    }
+}
+
+// Proves a witnessed record hashes to one of instance rows 1.. and contains instance row 0. Both
+// are a running product of (a - b) over the candidates that must end at zero.
+struct ExistsCircuit {
+    values: Vec<Value<Fp>>,
+    set_size: usize,
+}
+
+#[derive(Clone, Debug)]
+struct ExistsConfig {
+    hash: HashConfig,
+    a: Column<Advice>,
+    b: Column<Advice>,
+    product: Column<Advice>,
+    s_first: Selector,
+    s_step: Selector,
+    s_zero: Selector,
+}
+
+// One running product region: (a_i - b_i), with b_i read from an instance row, multiplied up.
+// `finish` constrains the product on the last row.
+fn assign_running_product(
+    config: &ExistsConfig,
+    mut layouter: impl Layouter<Fp>,
+    pairs: &[(AssignedCell<Fp, Fp>, usize)],
+    finish: impl Fn(&mut Region<'_, Fp>, usize, Value<Fp>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    layouter.assign_region(
+        || "running product",
+        |mut region| {
+            let mut product = Value::known(Fp::one());
+            for (row, (cell, instance_row)) in pairs.iter().enumerate() {
+                if row == 0 {
+                    config.s_first.enable(&mut region, row)?;
+                } else {
+                    config.s_step.enable(&mut region, row)?;
+                }
+                let a = cell.copy_advice(|| "a", &mut region, config.a, row)?;
+                let b = region.assign_advice_from_instance(|| "b", config.hash.expected, *instance_row, config.b, row)?;
+                product = product * a.value().zip(b.value()).map(|(a, b)| *a - *b);
+                region.assign_advice(|| "product", config.product, row, || product)?;
+            }
+            finish(&mut region, pairs.len() - 1, product)
+        },
+    )
+}
+
+fn assign_vanishing_product(
+    config: &ExistsConfig,
+    layouter: impl Layouter<Fp>,
+    pairs: &[(AssignedCell<Fp, Fp>, usize)],
+) -> Result<(), Error> {
+    assign_running_product(config, layouter, pairs, |region, row, _| config.s_zero.enable(region, row))
+}
+
+// Proves no element of any record hashing to instance rows 1.. equals instance row 0: the product
+// of (x - value) over every element has an inverse, so it isn't zero.
+struct NonMembershipCircuit {
+    records: Vec<Vec<Value<Fp>>>,
+}
+
+#[derive(Clone, Debug)]
+struct NonMembershipConfig {
+    product: ExistsConfig,
+    inverse: Column<Advice>,
+    s_nonzero: Selector,
+}
+
+impl Circuit<Fp> for NonMembershipCircuit {
+    type Config = NonMembershipConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            records: self.records.iter().map(|values| vec![Value::unknown(); values.len()]).collect(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let product = ExistsCircuit::configure(meta);
+        let inverse = meta.advice_column();
+        let s_nonzero = meta.selector();
+        let product_column = product.product;
+
+        meta.create_gate("product nonzero", |v_cells| {
+            let s = v_cells.query_selector(s_nonzero);
+            let product = v_cells.query_advice(product_column, Rotation::cur());
+            let inverse = v_cells.query_advice(inverse, Rotation::cur());
+
+            vec![s * (product * inverse - Expression::Constant(Fp::one()))]
+        });
+
+        NonMembershipConfig { product, inverse, s_nonzero }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let mut elements = Vec::new();
+        for (row, values) in self.records.iter().enumerate() {
+            let (digest, cells) = record_hash_gadget(
+                &config.product.hash.poseidon,
+                config.product.hash.input,
+                values,
+                layouter.namespace(|| format!("record {}", row)),
+            )?;
+            layouter.constrain_instance(digest.cell(), config.product.hash.expected, row + 1)?;
+            elements.extend(cells.into_iter().map(|cell| (cell, 0)));
+        }
+        if elements.is_empty() {
+            return Err(Error::Synthesis);
+        }
+        assign_running_product(&config.product, layouter.namespace(|| "elements"), &elements, |region, row, product| {
+            config.s_nonzero.enable(region, row)?;
+            let inverse = product.map(|p| p.invert().unwrap_or(Fp::zero()));
+            region.assign_advice(|| "inverse", config.inverse, row, || inverse)?;
+            Ok(())
+        })
+    }
+}
+
+impl Circuit<Fp> for ExistsCircuit {
+    type Config = ExistsConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+            set_size: self.set_size,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let a = meta.advice_column();
+        let b = meta.advice_column();
+        let product = meta.advice_column();
+        let s_first = meta.selector();
+        let s_step = meta.selector();
+        let s_zero = meta.selector();
+        meta.enable_equality(a);
+        meta.enable_equality(b);
+
+        meta.create_gate("product start", |v_cells| {
+            let s = v_cells.query_selector(s_first);
+            let a = v_cells.query_advice(a, Rotation::cur());
+            let b = v_cells.query_advice(b, Rotation::cur());
+            let product = v_cells.query_advice(product, Rotation::cur());
+
+            vec![s * (product - (a - b))]
+        });
+
+        meta.create_gate("product step", |v_cells| {
+            let s = v_cells.query_selector(s_step);
+            let a = v_cells.query_advice(a, Rotation::cur());
+            let b = v_cells.query_advice(b, Rotation::cur());
+            let prev = v_cells.query_advice(product, Rotation::prev());
+            let product = v_cells.query_advice(product, Rotation::cur());
+
+            vec![s * (product - prev * (a - b))]
+        });
+
+        meta.create_gate("product vanishes", |v_cells| {
+            let s = v_cells.query_selector(s_zero);
+            vec![s * v_cells.query_advice(product, Rotation::cur())]
+        });
+
+        ExistsConfig { hash, a, b, product, s_first, s_step, s_zero }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        if self.values.is_empty() || self.set_size == 0 {
+            return Err(Error::Synthesis);
+        }
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        let membership: Vec<_> = (0..self.set_size).map(|i| (digest.clone(), i + 1)).collect();
+        assign_vanishing_product(&config, layouter.namespace(|| "membership"), &membership)?;
+        let predicate: Vec<_> = cells.into_iter().map(|cell| (cell, 0)).collect();
+        assign_vanishing_product(&config, layouter.namespace(|| "predicate"), &predicate)
+    }
+}
+
+// Widest schema the quotient lookup supports: a field's data bytes sum to at most 255 * (W - 1),
+// and the quotient by 256 has to stay within the byte table.
+const MAX_SCHEMA_WIDTH: usize = 256;
+
+// Proves the record hashing to instance row 0 splits into `width`-byte fields whose last byte is
+// the sum of the others mod 256. Per field, acc runs over the data bytes and the check row has
+// acc - checksum = 256 * q; bytes and q are looked up in 0..256, so no side can wrap.
+struct SchemaCircuit {
+    values: Vec<Value<Fp>>,
+    width: usize,
+}
+
+#[derive(Clone, Debug)]
+struct SchemaConfig {
+    hash: HashConfig,
+    byte: Column<Advice>,
+    acc: Column<Advice>,
+    quotient: Column<Advice>,
+    bytes: TableColumn,
+    s_byte: Selector,
+    s_first: Selector,
+    s_step: Selector,
+    s_check: Selector,
+}
+
+impl Circuit<Fp> for SchemaCircuit {
+    type Config = SchemaConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+            width: self.width,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let byte = meta.advice_column();
+        let acc = meta.advice_column();
+        let quotient = meta.advice_column();
+        let bytes = meta.lookup_table_column();
+        let s_byte = meta.complex_selector();
+        let s_first = meta.selector();
+        let s_step = meta.selector();
+        let s_check = meta.complex_selector();
+        meta.enable_equality(byte);
+
+        meta.lookup(|v_cells| {
+            let s = v_cells.query_selector(s_byte);
+            vec![(s * v_cells.query_advice(byte, Rotation::cur()), bytes)]
+        });
+        meta.lookup(|v_cells| {
+            let s = v_cells.query_selector(s_check);
+            vec![(s * v_cells.query_advice(quotient, Rotation::cur()), bytes)]
+        });
+
+        meta.create_gate("schema start", |v_cells| {
+            let s = v_cells.query_selector(s_first);
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let byte = v_cells.query_advice(byte, Rotation::cur());
+
+            vec![s * (acc - byte)]
+        });
+
+        meta.create_gate("schema step", |v_cells| {
+            let s = v_cells.query_selector(s_step);
+            let cur = v_cells.query_advice(acc, Rotation::cur());
+            let next = v_cells.query_advice(acc, Rotation::next());
+            let byte = v_cells.query_advice(byte, Rotation::next());
+
+            vec![s * (next - cur - byte)]
+        });
+
+        meta.create_gate("schema check", |v_cells| {
+            let s = v_cells.query_selector(s_check);
+            let sum = v_cells.query_advice(acc, Rotation::prev());
+            let checksum = v_cells.query_advice(byte, Rotation::cur());
+            let quotient = v_cells.query_advice(quotient, Rotation::cur());
+
+            vec![s * (sum - checksum - quotient * Expression::Constant(Fp::from(256)))]
+        });
+
+        SchemaConfig { hash, byte, acc, quotient, bytes, s_byte, s_first, s_step, s_check }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        if self.width < 2 || self.values.len() % self.width != 0 {
+            return Err(Error::Synthesis);
+        }
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.hash.expected, 0)?;
+
+        layouter.assign_table(
+            || "bytes",
+            |mut table| {
+                for byte in 0..256u64 {
+                    table.assign_cell(|| "byte", config.bytes, byte as usize, || Value::known(Fp::from(byte)))?;
+                }
+                Ok(())
+            },
+        )?;
+
+        layouter.assign_region(
+            || "fields",
+            |mut region| {
+                for (field, chunk) in cells.chunks(self.width).enumerate() {
+                    let base = field * self.width;
+                    let mut acc = Value::known(0u64);
+                    for (offset, cell) in chunk.iter().enumerate() {
+                        let row = base + offset;
+                        config.s_byte.enable(&mut region, row)?;
+                        cell.copy_advice(|| "byte", &mut region, config.byte, row)?;
+                        let byte = cell.value().map(|v| fp_to_byte_lossy(v) as u64);
+                        if offset + 1 == self.width {
+                            config.s_check.enable(&mut region, row)?;
+                            // A field that doesn't conform leaves no quotient in 0..256 to find.
+                            let quotient = acc.zip(byte).map(|(sum, b)| Fp::from(sum.saturating_sub(b) / 256));
+                            region.assign_advice(|| "quotient", config.quotient, row, || quotient)?;
+                            region.assign_advice(|| "acc", config.acc, row, || Value::known(Fp::zero()))?;
+                        } else {
+                            if offset == 0 {
+                                config.s_first.enable(&mut region, row)?;
+                            }
+                            if offset + 2 < self.width {
+                                config.s_step.enable(&mut region, row)?;
+                            }
+                            acc = acc + byte;
+                            region.assign_advice(|| "acc", config.acc, row, || acc.map(Fp::from))?;
+                            region.assign_advice(|| "quotient", config.quotient, row, || Value::known(Fp::zero()))?;
+                        }
+                    }
+                }
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
+}
+
+// Proves record b is a permutation of record a: prod(a_i + gamma) == prod(b_i + gamma). gamma is
+// Poseidon of the two record hashes, so it is fixed only after both records are, and the verifier
+// recomputes it. Instance rows: id_a, id_b, hash_a, hash_b, gamma.
+struct PermutationCircuit {
+    a: Vec<Value<Fp>>,
+    b: Vec<Value<Fp>>,
+}
+
+#[derive(Clone, Debug)]
+struct PermutationConfig {
+    hash: HashConfig,
+    a: Column<Advice>,
+    b: Column<Advice>,
+    gamma: Column<Advice>,
+    prod_a: Column<Advice>,
+    prod_b: Column<Advice>,
+    s_first: Selector,
+    s_step: Selector,
+}
+
+impl Circuit<Fp> for PermutationCircuit {
+    type Config = PermutationConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: vec![Value::unknown(); self.a.len()],
+            b: vec![Value::unknown(); self.b.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let a = meta.advice_column();
+        let b = meta.advice_column();
+        let gamma = meta.advice_column();
+        let prod_a = meta.advice_column();
+        let prod_b = meta.advice_column();
+        let s_first = meta.selector();
+        let s_step = meta.selector();
+        for column in [a, b, gamma, prod_a, prod_b] {
+            meta.enable_equality(column);
+        }
+
+        meta.create_gate("product first", |v_cells| {
+            let a = v_cells.query_advice(a, Rotation::cur());
+            let b = v_cells.query_advice(b, Rotation::cur());
+            let gamma = v_cells.query_advice(gamma, Rotation::cur());
+            let prod_a = v_cells.query_advice(prod_a, Rotation::cur());
+            let prod_b = v_cells.query_advice(prod_b, Rotation::cur());
+            let s = v_cells.query_selector(s_first);
+
+            vec![
+                s.clone() * (prod_a - (a + gamma.clone())),
+                s * (prod_b - (b + gamma)),
+            ]
+        });
+
+        meta.create_gate("product step", |v_cells| {
+            let a = v_cells.query_advice(a, Rotation::cur());
+            let b = v_cells.query_advice(b, Rotation::cur());
+            let gamma = v_cells.query_advice(gamma, Rotation::cur());
+            let prod_a = v_cells.query_advice(prod_a, Rotation::cur());
+            let prod_b = v_cells.query_advice(prod_b, Rotation::cur());
+            let prev_a = v_cells.query_advice(prod_a, Rotation::prev());
+            let prev_b = v_cells.query_advice(prod_b, Rotation::prev());
+            let s = v_cells.query_selector(s_step);
+
+            vec![
+                s.clone() * (prod_a - prev_a * (a + gamma.clone())),
+                s * (prod_b - prev_b * (b + gamma)),
+            ]
+        });
+
+        PermutationConfig { hash, a, b, gamma, prod_a, prod_b, s_first, s_step }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let (digest_a, cells_a) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.a,
+            layouter.namespace(|| "hash a"),
+        )?;
+        layouter.constrain_instance(digest_a.cell(), config.hash.expected, 2)?;
+        let (digest_b, cells_b) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.b,
+            layouter.namespace(|| "hash b"),
+        )?;
+        layouter.constrain_instance(digest_b.cell(), config.hash.expected, 3)?;
+
+        layouter.assign_region(
+            || "grand product",
+            |mut region| {
+                let mut prod_a = Value::known(Fp::one());
+                let mut prod_b = Value::known(Fp::one());
+                let mut last = None;
+                for (idx, (a, b)) in cells_a.iter().zip(&cells_b).enumerate() {
+                    if idx == 0 {
+                        config.s_first.enable(&mut region, idx)?;
+                    } else {
+                        config.s_step.enable(&mut region, idx)?;
+                    }
+                    let gamma =
+                        region.assign_advice_from_instance(|| "gamma", config.hash.expected, 4, config.gamma, idx)?;
+                    a.copy_advice(|| "a", &mut region, config.a, idx)?;
+                    b.copy_advice(|| "b", &mut region, config.b, idx)?;
+                    prod_a = prod_a * (a.value().copied() + gamma.value().copied());
+                    prod_b = prod_b * (b.value().copied() + gamma.value().copied());
+                    let cell_a = region.assign_advice(|| "prod a", config.prod_a, idx, || prod_a)?;
+                    let cell_b = region.assign_advice(|| "prod b", config.prod_b, idx, || prod_b)?;
+                    last = Some((cell_a, cell_b));
+                }
+                if let Some((cell_a, cell_b)) = last {
+                    region.constrain_equal(cell_a.cell(), cell_b.cell())?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+// A record's place in the ledger: Poseidon(id, record_hash). prove_before commits to this rather
+// than the bare digest, so the id it ranges over is tied to the record.
+fn entry_commitment(id: u64, data: &[Fp]) -> Fp {
+    poseidon2(Fp::from(id), record_hash(data))
+}
+
+// Proves a witnessed id is below the threshold in instance row 0, where row 1 is
+// entry_commitment(id, record). Both id and threshold - 1 - id are decomposed into 64 bits, so
+// neither can wrap around the field.
+struct BeforeCircuit {
+    values: Vec<Value<Fp>>,
+    id: Value<Fp>,
+}
+
+#[derive(Clone, Debug)]
+struct BeforeConfig {
+    hash: HashConfig,
+    bit: Column<Advice>,
+    acc: Column<Advice>,
+    id: Column<Advice>,
+    threshold: Column<Advice>,
+    diff: Column<Advice>,
+    s_first: Selector,
+    s_step: Selector,
+    s_diff: Selector,
+}
+
+// Big-endian running sum over 64 boolean cells: acc_0 = b_0, acc_i = 2 * acc_{i-1} + b_i. The
+// last acc is constrained to `value`, which therefore lies in 0..2^64.
+fn assign_u64_range(
+    config: &BeforeConfig,
+    mut layouter: impl Layouter<Fp>,
+    value: &AssignedCell<Fp, Fp>,
+) -> Result<(), Error> {
+    layouter.assign_region(
+        || "u64 range",
+        |mut region| {
+            let bits = value.value().map(|v| {
+                let repr = v.to_repr();
+                u64::from_le_bytes(repr.as_ref()[..8].try_into().unwrap())
+            });
+            let mut acc = Value::known(Fp::zero());
+            let mut last = None;
+            for row in 0..64 {
+                if row == 0 {
+                    config.s_first.enable(&mut region, row)?;
+                } else {
+                    config.s_step.enable(&mut region, row)?;
+                }
+                let bit = bits.map(|b| Fp::from((b >> (63 - row)) & 1));
+                region.assign_advice(|| "bit", config.bit, row, || bit)?;
+                acc = acc * Value::known(Fp::from(2)) + bit;
+                last = Some(region.assign_advice(|| "acc", config.acc, row, || acc)?);
+            }
+            region.constrain_equal(last.unwrap().cell(), value.cell())
+        },
+    )
+}
+
+impl Circuit<Fp> for BeforeCircuit {
+    type Config = BeforeConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+            id: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let bit = meta.advice_column();
+        let acc = meta.advice_column();
+        let id = meta.advice_column();
+        let threshold = meta.advice_column();
+        let diff = meta.advice_column();
+        let s_first = meta.selector();
+        let s_step = meta.selector();
+        let s_diff = meta.selector();
+        for column in [acc, id, threshold, diff] {
+            meta.enable_equality(column);
+        }
+
+        meta.create_gate("range first", |v_cells| {
+            let s = v_cells.query_selector(s_first);
+            let bit = v_cells.query_advice(bit, Rotation::cur());
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let one = Expression::Constant(Fp::one());
+
+            vec![s.clone() * bit.clone() * (one - bit.clone()), s * (acc - bit)]
+        });
+
+        meta.create_gate("range step", |v_cells| {
+            let s = v_cells.query_selector(s_step);
+            let bit = v_cells.query_advice(bit, Rotation::cur());
+            let acc = v_cells.query_advice(acc, Rotation::cur());
+            let prev = v_cells.query_advice(acc, Rotation::prev());
+            let one = Expression::Constant(Fp::one());
+
+            vec![
+                s.clone() * bit.clone() * (one - bit.clone()),
+                s * (acc - prev * Expression::Constant(Fp::from(2)) - bit),
+            ]
+        });
+
+        meta.create_gate("before diff", |v_cells| {
+            let s = v_cells.query_selector(s_diff);
+            let id = v_cells.query_advice(id, Rotation::cur());
+            let threshold = v_cells.query_advice(threshold, Rotation::cur());
+            let diff = v_cells.query_advice(diff, Rotation::cur());
+
+            vec![s * (threshold - Expression::Constant(Fp::one()) - id - diff)]
+        });
+
+        BeforeConfig { hash, bit, acc, id, threshold, diff, s_first, s_step, s_diff }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let (digest, _) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+
+        let (id, diff) = layouter.assign_region(
+            || "before",
+            |mut region| {
+                config.s_diff.enable(&mut region, 0)?;
+                let threshold =
+                    region.assign_advice_from_instance(|| "threshold", config.hash.expected, 0, config.threshold, 0)?;
+                let id = region.assign_advice(|| "id", config.id, 0, || self.id)?;
+                let diff = threshold.value().copied() - Value::known(Fp::one()) - self.id;
+                let diff = region.assign_advice(|| "diff", config.diff, 0, || diff)?;
+                Ok((id, diff))
+            },
+        )?;
+        assign_u64_range(&config, layouter.namespace(|| "id range"), &id)?;
+        assign_u64_range(&config, layouter.namespace(|| "diff range"), &diff)?;
+
+        let hasher = PoseidonHash::<_, _, P128Pow5T3, ConstantLength<2>, 3, 2>::init(
+            Pow5Chip::construct(config.hash.poseidon.clone()),
+            layouter.namespace(|| "init entry"),
+        )?;
+        let entry = hasher.hash(layouter.namespace(|| "entry"), [id, digest])?;
+        layouter.constrain_instance(entry.cell(), config.hash.expected, 1)
+    }
+}
+
+// Proves the record hashing to instance row 5 is the one hashing to row 3 followed by the one
+// hashing to row 4, element for element, by copy constraints between the hashed cells. Rows 0-2
+// carry the three ids.
+struct ConcatCircuit {
+    a: Vec<Value<Fp>>,
+    b: Vec<Value<Fp>>,
+    ab: Vec<Value<Fp>>,
+}
+
+impl Circuit<Fp> for ConcatCircuit {
+    type Config = HashConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: vec![Value::unknown(); self.a.len()],
+            b: vec![Value::unknown(); self.b.len()],
+            ab: vec![Value::unknown(); self.ab.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        HashCircuit::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        if self.a.len() + self.b.len() != self.ab.len() {
+            return Err(Error::Synthesis);
+        }
+        let mut parts = Vec::new();
+        for (row, (name, values)) in [("hash a", &self.a), ("hash b", &self.b), ("hash ab", &self.ab)].into_iter().enumerate() {
+            let (digest, cells) =
+                record_hash_gadget(&config.poseidon, config.input, values, layouter.namespace(|| name))?;
+            layouter.constrain_instance(digest.cell(), config.expected, 3 + row)?;
+            parts.push(cells);
+        }
+        let ab = parts.pop().unwrap();
+
+        layouter.assign_region(
+            || "concat",
+            |mut region| {
+                for (part, whole) in parts.iter().flatten().zip(&ab) {
+                    region.constrain_equal(part.cell(), whole.cell())?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+// Element 0 of a record is its link slot (an owner key, say). Proves two records hashing to
+// instance rows 0 and 1 both carry the public value in row 2 there.
+struct LinkedCircuit {
+    a: Vec<Value<Fp>>,
+    b: Vec<Value<Fp>>,
+}
+
+impl Circuit<Fp> for LinkedCircuit {
+    type Config = HashConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            a: vec![Value::unknown(); self.a.len()],
+            b: vec![Value::unknown(); self.b.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        HashCircuit::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        for (row, values) in [&self.a, &self.b].into_iter().enumerate() {
+            let (digest, cells) = record_hash_gadget(
+                &config.poseidon,
+                config.input,
+                values,
+                layouter.namespace(|| format!("record {}", row)),
+            )?;
+            layouter.constrain_instance(digest.cell(), config.expected, row)?;
+            let slot = cells.first().ok_or(Error::Synthesis)?;
+            layouter.constrain_instance(slot.cell(), config.expected, 2)?;
+        }
+        Ok(())
+    }
+}
+
+// Proves knowledge of every record in a batch: record i hashes to instance row i.
+struct BatchCircuit {
+    records: Vec<Vec<Value<Fp>>>,
+}
+
+impl Circuit<Fp> for BatchCircuit {
+    type Config = HashConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            records: self.records.iter().map(|values| vec![Value::unknown(); values.len()]).collect(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        HashCircuit::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        for (row, values) in self.records.iter().enumerate() {
+            let (digest, _) = record_hash_gadget(
+                &config.poseidon,
+                config.input,
+                values,
+                layouter.namespace(|| format!("record {}", row)),
+            )?;
+            layouter.constrain_instance(digest.cell(), config.expected, row)?;
+        }
+        Ok(())
+    }
+}
+
+// Claims about one record that prove_combined can check in a single proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Statement {
+    // The elements add up to this value
+    Sum(Fp),
+    // Every element is a byte value (0..256)
+    ByteRange,
+}
+
+// Loads the record once through record_hash_gadget, whose digest is the first instance column's
+// only row, and applies each statement's constraints to copies of the same cells, so all claims
+// share one transcript and are about that record. Every Sum claim takes the next row of the
+// second instance column, in statement order.
+struct CombinedCircuit {
+    values: Vec<Value<Fp>>,
+    statements: Vec<Statement>,
+}
+
+#[derive(Clone, Debug)]
+struct CombinedConfig {
+    hash: HashConfig,
+    sum: SumConfig,
+    bytes: TableColumn,
+    s_byte: Selector,
+}
+
+impl Circuit<Fp> for CombinedCircuit {
+    type Config = CombinedConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            values: vec![Value::unknown(); self.values.len()],
+            statements: self.statements.clone(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let hash = HashCircuit::configure(meta);
+        let sum = SumConfig::configure(meta);
+        let bytes = meta.lookup_table_column();
+        let s_byte = meta.complex_selector();
+
+        meta.lookup(|v_cells| {
+            let value = v_cells.query_advice(sum.value, Rotation::cur());
+            let s = v_cells.query_selector(s_byte);
+
+            vec![(s * value, bytes)]
+        });
+
+        CombinedConfig { hash, sum, bytes, s_byte }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        layouter.assign_table(
+            || "bytes",
+            |mut table| {
+                for byte in 0..256u64 {
+                    table.assign_cell(|| "byte", config.bytes, byte as usize, || Value::known(Fp::from(byte)))?;
+                }
+                Ok(())
+            },
+        )?;
+
+        let (digest, cells) = record_hash_gadget(
+            &config.hash.poseidon,
+            config.hash.input,
+            &self.values,
+            layouter.namespace(|| "record hash"),
+        )?;
+        layouter.constrain_instance(digest.cell(), config.hash.expected, 0)?;
+
+        let check_sum = self.statements.iter().any(|s| matches!(s, Statement::Sum(_)));
+        let check_bytes = self.statements.contains(&Statement::ByteRange);
+        let total = layouter.assign_region(
+            || "record",
+            |mut region| {
+                let mut acc = Value::known(Fp::zero());
+                let mut last = None;
+                for (idx, cell) in cells.iter().enumerate() {
+                    if check_sum {
+                        if idx == 0 {
+                            config.sum.s_first.enable(&mut region, idx)?;
+                        } else {
+                            config.sum.s_step.enable(&mut region, idx)?;
+                        }
+                    }
+                    if check_bytes {
+                        config.s_byte.enable(&mut region, idx)?;
+                    }
+                    cell.copy_advice(|| "value", &mut region, config.sum.value, idx)?;
+                    acc = acc + cell.value().copied();
+                    last = Some(region.assign_advice(|| "acc", config.sum.acc, idx, || acc)?);
+                }
+
+                Ok(last)
+            },
+        )?;
+
+        let claims = self.statements.iter().filter(|s| matches!(s, Statement::Sum(_))).count();
+        if let Some(total) = total {
+            for row in 0..claims {
+                layouter.constrain_instance(total.cell(), config.sum.sum, row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn combined_instance(statements: &[Statement]) -> Vec<Fp> {
+    statements
+        .iter()
+        .filter_map(|s| match s {
+            Statement::Sum(claim) => Some(*claim),
+            Statement::ByteRange => None,
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct StoreStats {
+    record_count: usize,
+    total_original_bytes: usize,
+    total_field_elements: usize,
+}
+
+// Outcome of ZKIT::migrate for each record, in ascending id order.
+#[derive(Debug, Default)]
+struct MigrationReport {
+    results: Vec<(u64, Result<(), ZkitError>)>,
+}
+
+impl MigrationReport {
+    fn failed(&self) -> impl Iterator<Item = &(u64, Result<(), ZkitError>)> {
+        self.results.iter().filter(|(_, result)| result.is_err())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Status {
+    k: u32,
+    keys_ready: bool,
+    record_count: usize,
+    total_bytes: usize,
+    vk_fingerprint: Option<[u8; 32]>,
+    cached_proofs: usize,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "params k:       {}", self.k)?;
+        writeln!(f, "keys set up:    {}", if self.keys_ready { "yes" } else { "no" })?;
+        writeln!(f, "records:        {}", self.record_count)?;
+        writeln!(f, "total bytes:    {}", self.total_bytes)?;
+        match &self.vk_fingerprint {
+            Some(fingerprint) => writeln!(f, "vk fingerprint: {}", hex::encode(fingerprint))?,
+            None => writeln!(f, "vk fingerprint: -")?,
+        }
+        write!(f, "cached proofs:  {}", self.cached_proofs)
+    }
+}
+
+// Bytes of random data each loadtest cycle ingests.
+const LOADTEST_RECORD_BYTES: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LoadtestReport {
+    cycles: usize,
+    verified: usize,
+    failed: usize,
+    elapsed: Duration,
+    // Whole ingest/prove/verify cycle latencies
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+}
+
+impl std::fmt::Display for LoadtestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rate = self.cycles as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON);
+        writeln!(f, "cycles:     {} ({} verified, {} failed)", self.cycles, self.verified, self.failed)?;
+        writeln!(f, "elapsed:    {:?} ({:.2} cycles/s)", self.elapsed, rate)?;
+        write!(f, "latency:    p50 {:?}  p90 {:?}  p99 {:?}", self.p50, self.p90, self.p99)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CircuitStats {
+    advice_columns: usize,
+    fixed_columns: usize,
+    instance_columns: usize,
+    selectors: usize,
+    gates: usize,
+    estimated_rows: usize,
+}
+
+// One region as the floor planner laid it out, for dump_layout.
+#[derive(Clone, Debug, Default)]
+struct RegionLayout {
+    name: String,
+    rows: Option<(usize, usize)>,
+    cells: usize,
+    selectors: usize,
+    annotations: Vec<String>,
+}
+
+impl RegionLayout {
+    fn touch(&mut self, row: usize, annotation: String) {
+        self.rows = Some(self.rows.map_or((row, row), |(lo, hi)| (lo.min(row), hi.max(row))));
+        if !self.annotations.contains(&annotation) {
+            self.annotations.push(annotation);
+        }
+    }
+}
+
+// Records where synthesize puts things without evaluating any witness; instance values read
+// back as unknown, so witness-dependent layouts see the same shape keygen does.
+#[derive(Default)]
+struct LayoutRecorder {
+    regions: Vec<RegionLayout>,
+    current: Option<RegionLayout>,
+    copies: usize,
+}
+
+impl LayoutRecorder {
+    fn region(&mut self) -> &mut RegionLayout {
+        // Table loads and constants are assigned outside any region.
+        self.current.get_or_insert_with(|| RegionLayout {
+            name: "(outside regions)".to_string(),
+            ..RegionLayout::default()
+        })
+    }
+}
+
+impl Assignment<Fp> for LayoutRecorder {
+    fn enter_region<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.exit_region();
+        self.current = Some(RegionLayout {
+            name: name_fn().into(),
+            ..RegionLayout::default()
+        });
+    }
+
+    fn exit_region(&mut self) {
+        if let Some(region) = self.current.take() {
+            self.regions.push(region);
+        }
+    }
+
+    fn enable_selector<A, AR>(&mut self, annotation: A, _selector: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let region = self.region();
+        region.selectors += 1;
+        region.touch(row, annotation().into());
+        Ok(())
+    }
+
+    fn query_instance(&self, _column: Column<Instance>, _row: usize) -> Result<Value<Fp>, Error> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(&mut self, annotation: A, _column: Column<Advice>, row: usize, _to: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<Fp>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let region = self.region();
+        region.cells += 1;
+        region.touch(row, annotation().into());
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(&mut self, annotation: A, _column: Column<Fixed>, row: usize, _to: V) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<Fp>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let region = self.region();
+        region.cells += 1;
+        region.touch(row, annotation().into());
+        Ok(())
+    }
+
+    fn copy(&mut self, _left: Column<Any>, _left_row: usize, _right: Column<Any>, _right_row: usize) -> Result<(), Error> {
+        self.copies += 1;
+        Ok(())
+    }
+
+    fn fill_from_row(&mut self, _column: Column<Fixed>, _row: usize, _to: Value<Assigned<Fp>>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _gadget_name: Option<String>) {}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ProofCostEstimate {
+    proofs: usize,
+    duration: Duration,
+    memory_bytes: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ZkitEvent {
+    Ingested(u64),
+    ProofCreated { ok: bool },
+    ProofVerified { ok: bool },
+    IngestSkipped { path: PathBuf, reason: String },
+    ParamsGrown { from: u32, to: u32 },
+    WalWriteFailed { id: u64, reason: String },
+}
+
+type Hook = Box<dyn Fn(&ZkitEvent) + Send + Sync>;
+
+const DEFAULT_K: u32 = 8;
+
+#[derive(Default)]
+struct ZkitBuilder {
+    k: Option<u32>,
+    domain: Option<String>,
+    encoding: Option<Encoding>,
+    transcript: Option<TranscriptKind>,
+    verify_cache: Option<usize>,
+    auto_grow: bool,
+    capacity: usize,
+    fsync_snapshots: Option<bool>,
+    read_mostly: bool,
+    access_log: Option<usize>,
+    decode_mode: Option<DecodeMode>,
+    padding: Option<Padding>,
+    verify_mode: Option<VerifyMode>,
+    compress_proofs: bool,
+    max_record_bytes: Option<usize>,
+    proof_dir: Option<PathBuf>,
+    absorb_vk: bool,
+    proof_cache_limit: Option<usize>,
+    proof_retention: Option<usize>,
+    threads: Option<usize>,
+    encryption_key: Option<[u8; 32]>,
+}
+
+impl ZkitBuilder {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn k(mut self, k: u32) -> Self {
+        self.k = Some(k);
+        self
+    }
+
+    fn domain(mut self, tag: &str) -> Self {
+        self.domain = Some(tag.to_string());
+        self
+    }
+
+    fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    fn transcript(mut self, kind: TranscriptKind) -> Self {
+        self.transcript = Some(kind);
+        self
+    }
+
+    fn verify_cache(mut self, capacity: usize) -> Self {
+        self.verify_cache = Some(capacity);
+        self
+    }
+
+    fn auto_grow(mut self, enabled: bool) -> Self {
+        self.auto_grow = enabled;
+        self
+    }
+
+    // Snapshots are fsynced (file and parent directory) by default; turning it off trades crash
+    // durability for faster snapshots.
+    fn fsync_snapshots(mut self, enabled: bool) -> Self {
+        self.fsync_snapshots = Some(enabled);
+        self
+    }
+
+    fn padding(mut self, padding: Padding) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+
+    fn decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.decode_mode = Some(mode);
+        self
+    }
+
+    fn verify_mode(mut self, mode: VerifyMode) -> Self {
+        self.verify_mode = Some(mode);
+        self
+    }
+
+    fn compress_proofs(mut self, enabled: bool) -> Self {
+        self.compress_proofs = enabled;
+        self
+    }
+
+    // Ingests of more than `limit` original bytes fail with RecordTooLarge.
+    fn max_record_bytes(mut self, limit: usize) -> Self {
+        self.max_record_bytes = Some(limit);
+        self
+    }
+
+    // Proves and verifies on a pool of n threads of its own instead of rayon's global pool, which
+    // other libraries in the process may share. Callers' own threads still count: two concurrent
+    // proofs queue for the same n workers.
+    fn threads(mut self, n: usize) -> Self {
+        self.threads = Some(n);
+        self
+    }
+
+    // At most `limit` proofs are kept in memory, least recently used evicted first.
+    fn proof_cache_limit(mut self, limit: usize) -> Self {
+        self.proof_cache_limit = Some(limit);
+        self
+    }
+
+    // Encrypts records in snapshots and the WAL under this key; see RecordCipher. Records stay in
+    // plaintext in memory, so retrieve_data and proving are unaffected, and restoring or
+    // replaying a file needs an instance built with the same key.
+    fn encryption_key(mut self, key: [u8; 32]) -> Self {
+        self.encryption_key = Some(key);
+        self
+    }
+
+    // See ZKIT::set_proof_retention.
+    fn proof_retention(mut self, n: usize) -> Self {
+        self.proof_retention = Some(n);
+        self
+    }
+
+    // prove also looks proofs up in, and writes them to, this directory. It is created on build.
+    fn proof_cache_dir(mut self, path: &Path) -> Self {
+        self.proof_dir = Some(path.to_path_buf());
+        self
+    }
+
+    // See ZKIT::absorb_vk. Off by default so existing proofs keep verifying.
+    fn absorb_vk(mut self, enabled: bool) -> Self {
+        self.absorb_vk = enabled;
+        self
+    }
+
+    // Keeps the last `capacity` retrieve/prove/verify calls for ZKIT::access_log.
+    fn access_log(mut self, capacity: usize) -> Self {
+        self.access_log = Some(capacity);
+        self
+    }
+
+    // See ZKIT::published.
+    fn read_mostly(mut self, enabled: bool) -> Self {
+        self.read_mostly = enabled;
+        self
+    }
+
+    // Reserves room for this many records up front so bulk ingestion doesn't rehash.
+    fn with_capacity(mut self, n: usize) -> Self {
+        self.capacity = n;
+        self
+    }
+
+    fn build(self) -> Result<ZKIT, ZkitError> {
+        let mut zkit = ZKIT::with_k(self.k.unwrap_or(DEFAULT_K))?;
+        zkit.domain = self.domain;
+        if let Some(encoding) = self.encoding {
+            zkit.encoding = encoding;
+        }
+        if let Some(kind) = self.transcript {
+            zkit.transcript = kind;
+        }
+        zkit.verify_cache = self.verify_cache.map(|capacity| Mutex::new(VerifyCache::new(capacity)));
+        zkit.auto_grow = self.auto_grow;
+        zkit.storage = RwLock::new(HashMap::with_capacity(self.capacity));
+        if let Some(enabled) = self.fsync_snapshots {
+            zkit.fsync_snapshots = enabled;
+        }
+        if self.read_mostly {
+            zkit.published = Some(ArcSwap::from_pointee(HashMap::new()));
+        }
+        zkit.access_log = self.access_log.map(|capacity| Mutex::new(AccessLog::new(capacity)));
+        if let Some(mode) = self.decode_mode {
+            zkit.decode_mode = mode;
+        }
+        if let Some(padding) = self.padding {
+            zkit.padding = padding;
+        }
+        if let Some(mode) = self.verify_mode {
+            zkit.verify_mode = mode;
+        }
+        zkit.compress_proofs = self.compress_proofs;
+        zkit.max_record_bytes = self.max_record_bytes;
+        zkit.absorb_vk = self.absorb_vk;
+        zkit.proof_cache = RwLock::new(ProofCache::new(self.proof_cache_limit));
+        zkit.proof_cache.get_mut().unwrap().set_retention(self.proof_retention);
+        if let Some(mut key) = self.encryption_key {
+            zkit.cipher = Some(RecordCipher::new(&key));
+            key.zeroize();
+        }
+        if let Some(n) = self.threads {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(n)
+                .thread_name(|i| format!("zkit-prove-{}", i))
+                .build()
+                .map_err(|e| ZkitError::Config(format!("thread pool: {}", e)))?;
+            zkit.pool = Some(pool);
+        }
+        if let Some(dir) = self.proof_dir {
+            // A directory that can't be created just means every disk lookup misses.
+            let _ = fs::create_dir_all(&dir);
+            zkit.proof_dir = Some(dir);
+        }
+        Ok(zkit)
+    }
+}
+
+// Deployment settings in one place. Files ending in .toml are read as TOML, anything else as JSON.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct ZkitConfig {
+    k: Option<u32>,
+    encoding: Option<Encoding>,
+    transcript: Option<TranscriptKind>,
+    domain: Option<String>,
+    verify_cache: Option<usize>,
+    fsync_snapshots: Option<bool>,
+    decode_mode: Option<DecodeMode>,
+    padding: Option<Padding>,
+    verify_mode: Option<VerifyMode>,
+    // Restored on startup when the file exists
+    snapshot_path: Option<PathBuf>,
+}
+
+impl ZkitConfig {
+    fn load(path: &Path) -> Result<Self, ZkitError> {
+        let text = fs::read_to_string(path)?;
+        if path.extension().map_or(false, |ext| ext == "toml") {
+            toml::from_str(&text).map_err(|e| ZkitError::Config(e.to_string()))
+        } else {
+            serde_json::from_str(&text).map_err(|e| ZkitError::Config(e.to_string()))
+        }
+    }
+}
+
+// Maps a domain tag to the scalar absorbed at the start of every transcript.
+fn domain_scalar(tag: &str) -> Fp {
+    let hash = blake2b_simd::Params::new()
+        .hash_length(64)
+        .personal(b"ZKIT_Domain_Tag_")
+        .hash(tag.as_bytes());
+    Fp::from_bytes_wide(hash.as_array())
+}
+
+fn seed_scalar(seed: &[u8]) -> Fp {
+    let hash = blake2b_simd::Params::new()
+        .hash_length(64)
+        .personal(b"ZKIT_Trans_Seed_")
+        .hash(seed);
+    Fp::from_bytes_wide(hash.as_array())
+}
+
+fn vk_scalar(fingerprint: &[u8; 32]) -> Fp {
+    let hash = blake2b_simd::Params::new()
+        .hash_length(64)
+        .personal(b"ZKIT_VK_Absorb__")
+        .hash(fingerprint);
+    Fp::from_bytes_wide(hash.as_array())
+}
+
+fn inputs_hash(public_inputs: &[Fp]) -> [u8; 32] {
+    let mut state = blake2b_simd::Params::new()
+        .hash_length(32)
+        .personal(b"ZKIT_Public_Ins_")
+        .to_state();
+    for x in public_inputs {
+        state.update(x.to_repr().as_ref());
+    }
+    state.finalize().as_bytes().try_into().unwrap()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TranscriptKind {
+    Blake2b,
+    Keccak,
+}
+
+// Keccak-256 transcript for verifiers that can't run Blake2b (e.g. EVM). It mirrors halo2's Blake2b
+// transcript: prefixed absorption, and a 512-bit challenge built from two domain-separated squeezes.
+const KECCAK_PREFIX_CHALLENGE: u8 = 0;
+const KECCAK_PREFIX_POINT: u8 = 1;
+const KECCAK_PREFIX_SCALAR: u8 = 2;
+
+struct KeccakWrite<W: Write, C: CurveAffine> {
+    state: Keccak256,
+    writer: W,
+    _marker: PhantomData<C>,
+}
+
+struct KeccakRead<R: Read, C: CurveAffine> {
+    state: Keccak256,
+    reader: R,
+    _marker: PhantomData<C>,
+}
+
+impl<W: Write, C: CurveAffine> KeccakWrite<W, C> {
+    fn init(writer: W) -> Self {
+        Self {
+            state: Keccak256::new_with_prefix(b"ZKIT_Keccak_Transcript"),
+            writer,
+            _marker: PhantomData,
+        }
+    }
+
+    fn finalize(self) -> W {
+        self.writer
+    }
+}
+
+impl<R: Read, C: CurveAffine> KeccakRead<R, C> {
+    fn init(reader: R) -> Self {
+        Self {
+            state: Keccak256::new_with_prefix(b"ZKIT_Keccak_Transcript"),
+            reader,
+            _marker: PhantomData,
+        }
+    }
+}
+
+fn keccak_squeeze<C: CurveAffine>(state: &mut Keccak256) -> Challenge255<C> {
+    state.update([KECCAK_PREFIX_CHALLENGE]);
+    let mut bytes = [0u8; 64];
+    let mut lo = state.clone();
+    lo.update([0u8]);
+    bytes[..32].copy_from_slice(&lo.finalize());
+    let mut hi = state.clone();
+    hi.update([1u8]);
+    bytes[32..].copy_from_slice(&hi.finalize());
+    Challenge255::<C>::new(&bytes)
+}
+
+fn keccak_absorb_point<C: CurveAffine>(state: &mut Keccak256, point: C) -> io::Result<()> {
+    state.update([KECCAK_PREFIX_POINT]);
+    let coords: Coordinates<C> = Option::from(point.coordinates()).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::Other, "cannot write points at infinity to the transcript")
+    })?;
+    state.update(coords.x().to_repr().as_ref());
+    state.update(coords.y().to_repr().as_ref());
+    Ok(())
+}
+
+fn keccak_absorb_scalar<C: CurveAffine>(state: &mut Keccak256, scalar: C::Scalar) {
+    state.update([KECCAK_PREFIX_SCALAR]);
+    state.update(scalar.to_repr().as_ref());
+}
+
+impl<W: Write, C: CurveAffine> Transcript<C, Challenge255<C>> for KeccakWrite<W, C> {
+    fn squeeze_challenge(&mut self) -> Challenge255<C> {
+        keccak_squeeze(&mut self.state)
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        keccak_absorb_point(&mut self.state, point)
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        keccak_absorb_scalar::<C>(&mut self.state, scalar);
+        Ok(())
+    }
+}
+
+impl<W: Write, C: CurveAffine> TranscriptWrite<C, Challenge255<C>> for KeccakWrite<W, C> {
+    fn write_point(&mut self, point: C) -> io::Result<()> {
+        self.common_point(point)?;
+        self.writer.write_all(point.to_bytes().as_ref())
+    }
+
+    fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.common_scalar(scalar)?;
+        self.writer.write_all(scalar.to_repr().as_ref())
+    }
+}
+
+impl<R: Read, C: CurveAffine> Transcript<C, Challenge255<C>> for KeccakRead<R, C> {
+    fn squeeze_challenge(&mut self) -> Challenge255<C> {
+        keccak_squeeze(&mut self.state)
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        keccak_absorb_point(&mut self.state, point)
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        keccak_absorb_scalar::<C>(&mut self.state, scalar);
+        Ok(())
+    }
+}
+
+impl<R: Read, C: CurveAffine> TranscriptRead<C, Challenge255<C>> for KeccakRead<R, C> {
+    fn read_point(&mut self) -> io::Result<C> {
+        let mut compressed = C::Repr::default();
+        self.reader.read_exact(compressed.as_mut())?;
+        let point: C = Option::from(C::from_bytes(&compressed)).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "invalid point encoding in proof")
+        })?;
+        self.common_point(point)?;
+        Ok(point)
+    }
+
+    fn read_scalar(&mut self) -> io::Result<C::Scalar> {
+        let mut data = <C::Scalar as PrimeField>::Repr::default();
+        self.reader.read_exact(data.as_mut())?;
+        let scalar: C::Scalar = Option::from(C::Scalar::from_repr(data)).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "invalid field element encoding in proof")
+        })?;
+        self.common_scalar(scalar)?;
+        Ok(scalar)
+    }
+}
+
+// Handle to a proof running on a worker thread.
+struct ProofTask {
+    rx: mpsc::Receiver<Result<Vec<u8>, ZkitError>>,
+}
+
+impl ProofTask {
+    fn wait_timeout(&self, timeout: Duration) -> Option<Result<Vec<u8>, ZkitError>> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => Some(Err(ZkitError::WorkerFailed)),
+        }
+    }
+
+    // halo2 can't be interrupted mid-proof, so cancelling abandons the worker and its result is
+    // dropped when it finishes. Proving only reads the store and keys, so nothing is left half-written.
+    fn cancel(self) {}
+}
+
+// Collects additive shares of a record's witness from several parties: the witness is the
+// element-wise sum of all shares. finalize proves it only if the sum is the stored record, so a
+// wrong share surfaces as ChecksumMismatch rather than a proof of something else.
+struct ProofSession<'a> {
+    zkit: &'a ZKIT,
+    id: u64,
+    sum: Vec<Fp>,
+}
+
+impl ProofSession<'_> {
+    fn contribute(&mut self, share: &[Fp]) -> Result<(), ZkitError> {
+        if share.len() != self.sum.len() {
+            return Err(ZkitError::LengthMismatch);
+        }
+        for (acc, &x) in self.sum.iter_mut().zip(share) {
+            *acc += x;
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, ZkitError> {
+        let record = self.zkit.record(self.id)?;
+        if record_checksum(&self.sum) != record.checksum {
+            return Err(ZkitError::ChecksumMismatch(self.id));
+        }
+        let circuit: ExampleCircuit<Fp> = ExampleCircuit {
+            data: std::mem::take(&mut self.sum),
+            lookup: None,
+            _marker: PhantomData,
+        };
+        self.zkit.create_proof(&circuit)
+    }
+}
+
+impl Drop for ProofSession<'_> {
+    fn drop(&mut self) {
+        zeroize_elements(&mut self.sum);
+    }
+}
+
+#[derive(Debug, Default)]
+struct JobState {
+    running: usize,
+    closed: bool,
+}
+
+// Held by a worker for the lifetime of one threaded proof or verification; dropping it, including
+// on unwind, marks the job finished and wakes a waiting shutdown.
+struct JobGuard(Arc<ZKIT>);
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        let mut jobs = self.0.jobs.lock().unwrap();
+        jobs.running -= 1;
+        if jobs.running == 0 {
+            self.0.jobs_done.notify_all();
+        }
+    }
+}
+
+fn fingerprint_vk(vk: &VerifyingKey<Fp>) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    vk.write(&mut bytes).expect("writing to a Vec cannot fail");
+    blake2b_simd::Params::new()
+        .hash_length(32)
+        .personal(b"ZKIT_VK_Finger__")
+        .hash(&bytes)
+        .as_bytes()
+        .try_into()
+        .unwrap()
+}
+
+// Everything absorbed ahead of the halo2 proof itself; prover and verifier must agree on it.
+fn absorb_preamble(
+    domain: Option<&str>,
+    vk_fingerprint: Option<&[u8; 32]>,
+    binding: &TranscriptBinding,
+    transcript: &mut impl Transcript<EqAffine, Challenge255<EqAffine>>,
+) -> Result<(), ZkitError> {
+    if let Some(seed) = &binding.seed {
+        transcript.common_scalar(seed_scalar(seed))?;
+    }
+    if let Some(tag) = domain {
+        transcript.common_scalar(domain_scalar(tag))?;
+    }
+    if let Some(fingerprint) = vk_fingerprint {
+        transcript.common_scalar(vk_scalar(fingerprint))?;
+    }
+    if let Some(timestamp) = binding.timestamp {
+        transcript.common_scalar(Fp::from(timestamp))?;
+    }
+    Ok(())
+}
+
+fn verify_halo2(
+    params: &Params<Fp>,
+    vk: &VerifyingKey<Fp>,
+    instances: &[&[Fp]],
+    transcript: &mut impl TranscriptRead<EqAffine, Challenge255<EqAffine>>,
+) -> Result<bool, ZkitError> {
+    // Proof bytes are untrusted. Everything before this point is bounds-checked, but halo2
+    // asserts on some malformed inputs instead of returning an error, so a panic here is
+    // reported as a malformed proof rather than taking the caller down.
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        verify_proof(params, vk, &[instances], &mut *transcript)
+    }))
+    .map_err(|_| ZkitError::MalformedProof)?;
+    match outcome {
+        Ok(()) => Ok(true),
+        // The proof decoded fine but the verification equations don't hold.
+        Err(Error::ConstraintSystemFailure) | Err(Error::Opening) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Splits a proof into its halo2 transcript and the values bound into it. This is the one place
+// verification dispatches on the wire version: v1 has no frame, v2 and v3 are framed, and
+// read_proof rejects any other framed version as UnsupportedVersion.
+fn proof_transcript(proof: &[u8]) -> Result<(Vec<u8>, TranscriptBinding), ZkitError> {
+    if !proof.starts_with(&PROOF_MAGIC) {
+        return Ok((proof.to_vec(), TranscriptBinding::default()));
+    }
+    let framed = read_proof(proof)?;
+    let binding = match framed.version {
+        PROOF_VERSION => TranscriptBinding::default(),
+        PROOF_VERSION_TIMESTAMPED => TranscriptBinding {
+            timestamp: framed.timestamp,
+            seed: None,
+        },
+        version => return Err(ZkitError::UnsupportedVersion(version)),
+    };
+    Ok((framed.transcript, binding))
+}
+
+type StandaloneKeys = Arc<(Params<Fp>, VerifyingKey<Fp>)>;
+
+type StandaloneKeyId = ([u8; 32], [u8; 32]);
+
+// Parsed params and vk for verify_standalone, keyed by a digest of the params bytes and the vk's
+// fingerprint, least recently used first. Past STANDALONE_KEYS_CAP the front one is dropped, so a
+// caller cycling through many keys costs reparses, not memory.
+static STANDALONE_KEYS: OnceLock<Mutex<VecDeque<(StandaloneKeyId, StandaloneKeys)>>> = OnceLock::new();
+const STANDALONE_KEYS_CAP: usize = 8;
+static STANDALONE_KEY_LOADS: AtomicU64 = AtomicU64::new(0);
+static DISK_PROOF_TMP: AtomicU64 = AtomicU64::new(0);
+
+// How many times verify_standalone has had to deserialize a params/vk pair.
+fn standalone_key_loads() -> u64 {
+    STANDALONE_KEY_LOADS.load(Ordering::Relaxed)
+}
+
+fn standalone_keys(params_bytes: &[u8], vk_bytes: &[u8]) -> Result<StandaloneKeys, ZkitError> {
+    let digest = |personal: &[u8], bytes: &[u8]| -> [u8; 32] {
+        blake2b_simd::Params::new()
+            .hash_length(32)
+            .personal(personal)
+            .hash(bytes)
+            .as_bytes()
+            .try_into()
+            .unwrap()
+    };
+    // Same personalization as fingerprint_vk, so the key matches ZKIT::vk_fingerprint.
+    let key = (digest(b"ZKIT_Params_Hash", params_bytes), digest(b"ZKIT_VK_Finger__", vk_bytes));
+    let cache = STANDALONE_KEYS.get_or_init(|| Mutex::new(VecDeque::new()));
+    {
+        let mut cache = cache.lock().unwrap();
+        if let Some(pos) = cache.iter().position(|(id, _)| *id == key) {
+            let hit = cache.remove(pos).unwrap();
+            let keys = hit.1.clone();
+            cache.push_back(hit);
+            return Ok(keys);
+        }
+    }
+    // Parsed outside the lock; two threads racing on a new key both parse it and one insert wins.
+    let params = Params::read(&mut &params_bytes[..])?;
+    let vk = VerifyingKey::read::<_, ExampleCircuit<Fp>>(&mut &vk_bytes[..], &params)?;
+    STANDALONE_KEY_LOADS.fetch_add(1, Ordering::Relaxed);
+    let keys = Arc::new((params, vk));
+    let mut cache = cache.lock().unwrap();
+    if let Some((_, existing)) = cache.iter().find(|(id, _)| *id == key) {
+        return Ok(existing.clone());
+    }
+    cache.push_back((key, keys.clone()));
+    if cache.len() > STANDALONE_KEYS_CAP {
+        cache.pop_front();
+    }
+    Ok(keys)
+}
+
+// Verifies an instance-circuit proof from serialized params and vk alone, for verifier services
+// that never hold a store. Covers the default setup: Blake2b transcript and no domain tag.
+fn verify_standalone(
+    params_bytes: &[u8],
+    vk_bytes: &[u8],
+    public_inputs: &[Fp],
+    proof: &[u8],
+) -> Result<bool, ZkitError> {
+    if proof.len() < MIN_PROOF_LEN {
+        return Err(ZkitError::EmptyProof);
+    }
+    let keys = standalone_keys(params_bytes, vk_bytes)?;
+    let (params, vk) = &*keys;
+    let (transcript, binding) = proof_transcript(proof)?;
+    let columns = route_public_inputs(public_inputs, vk.cs().num_instance_columns());
+    let instances: Vec<&[Fp]> = columns.iter().map(Vec::as_slice).collect();
+
+    verify_transcript(params, vk, TranscriptKind::Blake2b, None, None, &instances, &transcript, &binding, VerifyMode::Strict)
+}
+
+// Verifier package layout: magic | package version (u8) | newest proof version (u8) |
+// instance columns (u32) | transcript (u8: 0 Blake2b, 1 Keccak) | absorb vk (u8) | domain tag
+// length (u32, 0 for none) | domain tag | params length (u64) | params | vk length (u64) | vk.
+// Integers are LE. Version 1 has no absorb byte and never absorbs.
+const PACKAGE_MAGIC: [u8; 4] = *b"ZKVP";
+const PACKAGE_VERSION: u8 = 2;
+
+fn write_package_blob(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    out.write_all(bytes)
+}
+
+fn read_package_blob(input: &mut impl Read) -> Result<Vec<u8>, ZkitError> {
+    let len = read_u64(input)?;
+    let mut bytes = Vec::new();
+    if input.take(len).read_to_end(&mut bytes)? as u64 != len {
+        return Err(ZkitError::BadPackage("truncated".to_string()));
+    }
+    Ok(bytes)
+}
+
+// Reading a vk needs the circuit type, and the instance column count is part of that type.
+fn read_example_vk(bytes: &[u8], params: &Params<Fp>, columns: usize) -> Result<VerifyingKey<Fp>, ZkitError> {
+    let reader = &mut &bytes[..];
+    let vk = match columns {
+        1 => VerifyingKey::read::<_, ExampleCircuit<Fp, 1>>(reader, params)?,
+        2 => VerifyingKey::read::<_, ExampleCircuit<Fp, 2>>(reader, params)?,
+        3 => VerifyingKey::read::<_, ExampleCircuit<Fp, 3>>(reader, params)?,
+        4 => VerifyingKey::read::<_, ExampleCircuit<Fp, 4>>(reader, params)?,
+        n => return Err(ZkitError::BadPackage(format!("unsupported instance column count {}", n))),
+    };
+    Ok(vk)
+}
+
+// Everything needed to check instance-circuit proofs, and nothing that can make them.
+struct VerifierOnlyZkit {
+    params: Params<Fp>,
+    vk: VerifyingKey<Fp>,
+    transcript: TranscriptKind,
+    domain: Option<String>,
+    instance_columns: usize,
+    // Set when the exporting instance absorbs its vk fingerprint into every transcript
+    absorbed_vk: Option<[u8; 32]>,
+}
+
+impl VerifierOnlyZkit {
+    fn verify_proof(&self, proof: &[u8]) -> Result<bool, ZkitError> {
+        self.verify_proof_with_inputs(proof, &[])
+    }
+
+    fn verify_proof_with_inputs(&self, proof: &[u8], public_inputs: &[Fp]) -> Result<bool, ZkitError> {
+        if proof.len() < MIN_PROOF_LEN {
+            return Err(ZkitError::EmptyProof);
+        }
+        let (transcript, binding) = proof_transcript(proof)?;
+        let columns = route_public_inputs(public_inputs, self.instance_columns);
+        let instances: Vec<&[Fp]> = columns.iter().map(Vec::as_slice).collect();
+        verify_transcript(
+            &self.params,
+            &self.vk,
+            self.transcript,
+            self.domain.as_deref(),
+            self.absorbed_vk.as_ref(),
+            &instances,
+            &transcript,
+            &binding,
+            VerifyMode::Strict,
+        )
+    }
+}
+
+fn import_verifier_package(path: &Path) -> Result<VerifierOnlyZkit, ZkitError> {
+    let mut input = io::BufReader::new(File::open(path)?);
+    let mut header = [0u8; 6];
+    input.read_exact(&mut header)?;
+    if header[..4] != PACKAGE_MAGIC {
+        return Err(ZkitError::BadPackage("bad magic".to_string()));
+    }
+    let version = header[4];
+    if version == 0 || version > PACKAGE_VERSION {
+        return Err(ZkitError::UnsupportedVersion(version));
+    }
+    if header[5] > PROOF_VERSION_TIMESTAMPED {
+        return Err(ZkitError::UnsupportedVersion(header[5]));
+    }
+    let mut word = [0u8; 4];
+    input.read_exact(&mut word)?;
+    let instance_columns = u32::from_le_bytes(word) as usize;
+    let mut kind = [0u8; 1];
+    input.read_exact(&mut kind)?;
+    let transcript = match kind[0] {
+        0 => TranscriptKind::Blake2b,
+        1 => TranscriptKind::Keccak,
+        other => return Err(ZkitError::BadPackage(format!("unknown transcript {}", other))),
+    };
+    let mut absorb_vk = [0u8; 1];
+    if version >= 2 {
+        input.read_exact(&mut absorb_vk)?;
+    }
+    let absorb_vk = match absorb_vk[0] {
+        0 => false,
+        1 => true,
+        other => return Err(ZkitError::BadPackage(format!("bad absorb flag {}", other))),
+    };
+    input.read_exact(&mut word)?;
+    let mut tag = Vec::new();
+    let tag_len = u32::from_le_bytes(word) as u64;
+    if (&mut input).take(tag_len).read_to_end(&mut tag)? as u64 != tag_len {
+        return Err(ZkitError::BadPackage("truncated".to_string()));
+    }
+    let domain = if tag.is_empty() {
+        None
+    } else {
+        let tag = String::from_utf8(tag).map_err(|_| ZkitError::BadPackage("domain tag is not UTF-8".to_string()))?;
+        Some(tag)
+    };
+    let params = Params::read(&mut &read_package_blob(&mut input)?[..])?;
+    let vk = read_example_vk(&read_package_blob(&mut input)?, &params, instance_columns)?;
+    let absorbed_vk = absorb_vk.then(|| fingerprint_vk(&vk));
+    Ok(VerifierOnlyZkit {
+        params,
+        vk,
+        transcript,
+        domain,
+        instance_columns,
+        absorbed_vk,
+    })
+}
+
+// One transcript operation as the verifier performs it, reported by verify_proof_interactive.
+#[derive(Clone, Debug)]
+enum TranscriptStep {
+    CommonPoint(EqAffine),
+    CommonScalar(Fp),
+    ReadPoint(EqAffine),
+    ReadScalar(Fp),
+    Challenge(Fp),
+}
+
+// Passes every operation through to `inner` and reports it to `observe` afterwards, so the
+// challenges seen are exactly the ones the wrapped transcript derived.
+struct ObservedTranscript<T, F> {
+    inner: T,
+    observe: F,
+}
+
+impl<T, F> Transcript<EqAffine, Challenge255<EqAffine>> for ObservedTranscript<T, F>
+where
+    T: TranscriptRead<EqAffine, Challenge255<EqAffine>>,
+    F: FnMut(TranscriptStep),
+{
+    fn squeeze_challenge(&mut self) -> Challenge255<EqAffine> {
+        let challenge = self.inner.squeeze_challenge();
+        (self.observe)(TranscriptStep::Challenge(challenge.get_scalar()));
+        challenge
+    }
+
+    fn common_point(&mut self, point: EqAffine) -> io::Result<()> {
+        self.inner.common_point(point)?;
+        (self.observe)(TranscriptStep::CommonPoint(point));
+        Ok(())
+    }
+
+    fn common_scalar(&mut self, scalar: Fp) -> io::Result<()> {
+        self.inner.common_scalar(scalar)?;
+        (self.observe)(TranscriptStep::CommonScalar(scalar));
+        Ok(())
+    }
+}
+
+impl<T, F> TranscriptRead<EqAffine, Challenge255<EqAffine>> for ObservedTranscript<T, F>
+where
+    T: TranscriptRead<EqAffine, Challenge255<EqAffine>>,
+    F: FnMut(TranscriptStep),
+{
+    fn read_point(&mut self) -> io::Result<EqAffine> {
+        let point = self.inner.read_point()?;
+        (self.observe)(TranscriptStep::ReadPoint(point));
+        Ok(point)
+    }
+
+    fn read_scalar(&mut self) -> io::Result<Fp> {
+        let scalar = self.inner.read_scalar()?;
+        (self.observe)(TranscriptStep::ReadScalar(scalar));
+        Ok(scalar)
+    }
+}
+
+// The verifier's whole transcript over a bare halo2 proof: preamble, then the halo2 checks.
+fn verify_transcript(
+    params: &Params<Fp>,
+    vk: &VerifyingKey<Fp>,
+    kind: TranscriptKind,
+    domain: Option<&str>,
+    vk_fingerprint: Option<&[u8; 32]>,
+    instances: &[&[Fp]],
+    proof: &[u8],
+    binding: &TranscriptBinding,
+    mode: VerifyMode,
+) -> Result<bool, ZkitError> {
+    let mut rest = proof;
+    let ok = match kind {
+        TranscriptKind::Blake2b => {
+            let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&mut rest);
+            absorb_preamble(domain, vk_fingerprint, binding, &mut transcript)?;
+            verify_halo2(params, vk, instances, &mut transcript)?
+        }
+        TranscriptKind::Keccak => {
+            let mut transcript = KeccakRead::init(&mut rest);
+            absorb_preamble(domain, vk_fingerprint, binding, &mut transcript)?;
+            verify_halo2(params, vk, instances, &mut transcript)?
+        }
+    };
+    if mode == VerifyMode::Strict && !rest.is_empty() {
+        return Err(ZkitError::MalformedProof);
+    }
+    Ok(ok)
+}
+
+fn verify_cache_key(vk_fingerprint: &[u8; 32], public_inputs: &[Fp], proof: &[u8], mode: VerifyMode) -> [u8; 32] {
+    blake2b_simd::Params::new()
+        .hash_length(32)
+        .personal(b"ZKIT_VerifyCache")
+        .to_state()
+        .update(&[mode as u8])
+        .update(vk_fingerprint)
+        .update(&inputs_hash(public_inputs))
+        .update(proof)
+        .finalize()
+        .as_bytes()
+        .try_into()
+        .unwrap()
+}
+
+// A cached proof together with what it was made against, so it can be re-checked later.
+#[derive(Clone)]
+struct CachedProof {
+    proof: Vec<u8>,
+    public_inputs: Vec<Fp>,
+    record_checksum: [u8; 32],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AccessKind {
+    Retrieve,
+    Prove,
+    Verify,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct AccessEntry {
+    kind: AccessKind,
+    // Verification isn't tied to a stored record, so it logs no id.
+    id: Option<u64>,
+    timestamp: u64,
+}
+
+// Ring buffer of the most recent accesses; the oldest entry is dropped once capacity is reached.
+struct AccessLog {
+    capacity: usize,
+    entries: VecDeque<AccessEntry>,
+}
+
+impl AccessLog {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn push(&mut self, entry: AccessEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+// Bounded FIFO of verification outcomes; the oldest entry is evicted once capacity is reached.
+struct VerifyCache {
+    capacity: usize,
+    entries: HashMap<[u8; 32], bool>,
+    order: VecDeque<[u8; 32]>,
+    hits: u64,
+}
+
+impl VerifyCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            hits: 0,
+        }
+    }
+
+    fn get(&mut self, key: &[u8; 32]) -> Option<bool> {
+        let ok = self.entries.get(key).copied();
+        if ok.is_some() {
+            self.hits += 1;
+        }
+        ok
+    }
+
+    fn insert(&mut self, key: [u8; 32], ok: bool) {
+        if self.capacity == 0 || self.entries.contains_key(&key) {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, ok);
+        self.order.push_back(key);
+    }
+}
+
+type ProofCacheKey = (u64, [u8; 32]);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct CacheStats {
+    entries: usize,
+    limit: Option<usize>,
+    retention: Option<usize>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+// Cached proofs keyed by (record id, public inputs hash). With a limit, inserting past it evicts
+// the least recently used entry. Retention bounds the count by age instead: past it the oldest
+// proof goes however recently it was read. Reproving an entry makes it new again.
+//
+// Every insert and hit takes a fresh stamp from `clock`. `created` maps each entry's insert stamp
+// to its key. `order` maps a stamp to its key too, but a hit only records its stamp in the slot, so
+// get works under a read lock; trim re-files a stale entry under its latest stamp when it reaches
+// the front, which costs each hit at most one extra BTreeMap insert.
+struct ProofCache {
+    limit: Option<usize>,
+    retention: Option<usize>,
+    entries: HashMap<ProofCacheKey, CachedSlot>,
+    order: BTreeMap<u64, ProofCacheKey>,
+    created: BTreeMap<u64, ProofCacheKey>,
+    clock: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: u64,
+}
+
+struct CachedSlot {
+    entry: CachedProof,
+    // The stamp of the last insert or hit
+    used: AtomicU64,
+    // The stamp this entry is filed under in `order`; behind `used` after a hit
+    placed: u64,
+    created: u64,
+}
+
+impl ProofCache {
+    fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            retention: None,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            created: BTreeMap::new(),
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: 0,
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn get(&self, key: &ProofCacheKey) -> Option<&CachedProof> {
+        match self.entries.get(key) {
+            Some(slot) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                slot.used.fetch_max(self.tick(), Ordering::Relaxed);
+                Some(&slot.entry)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: ProofCacheKey, entry: CachedProof) {
+        if let Some(old) = self.entries.remove(&key) {
+            self.order.remove(&old.placed);
+            self.created.remove(&old.created);
+        }
+        let stamp = self.tick();
+        self.order.insert(stamp, key);
+        self.created.insert(stamp, key);
+        self.entries.insert(
+            key,
+            CachedSlot {
+                entry,
+                used: AtomicU64::new(stamp),
+                placed: stamp,
+                created: stamp,
+            },
+        );
+        self.trim();
+    }
+
+    fn set_retention(&mut self, retention: Option<usize>) {
+        self.retention = retention;
+        self.trim();
+    }
+
+    fn evict(&mut self, key: &ProofCacheKey) {
+        if let Some(slot) = self.entries.remove(key) {
+            self.order.remove(&slot.placed);
+            self.created.remove(&slot.created);
+            self.evictions += 1;
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.limit.is_some_and(|limit| self.entries.len() > limit) {
+            let (stamp, least_recent) = match self.order.pop_first() {
+                Some(front) => front,
+                None => break,
+            };
+            let slot = match self.entries.get_mut(&least_recent) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            let used = *slot.used.get_mut();
+            if used != stamp {
+                // Read since it was filed: move it to where its last hit puts it and look again.
+                slot.placed = used;
+                self.order.insert(used, least_recent);
+                continue;
+            }
+            self.evict(&least_recent);
+        }
+        while self.retention.is_some_and(|retention| self.entries.len() > retention) {
+            match self.created.first_key_value().map(|(_, key)| *key) {
+                Some(oldest) => self.evict(&oldest),
+                None => break,
+            }
+        }
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&ProofCacheKey, &mut CachedProof) -> bool) {
+        let (order, created) = (&mut self.order, &mut self.created);
+        self.entries.retain(|key, slot| {
+            let kept = keep(key, &mut slot.entry);
+            if !kept {
+                order.remove(&slot.placed);
+                created.remove(&slot.created);
+            }
+            kept
+        });
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.created.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&ProofCacheKey, &CachedProof)> {
+        self.entries.iter().map(|(key, slot)| (key, &slot.entry))
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            limit: self.limit,
+            retention: self.retention,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions,
+        }
+    }
+}
+
+// Key sizes in bytes. vk is the serialized length. halo2 0.1 can't serialize a proving key, so
+// pk_estimate is counted rather than measured: the vk plus the polynomials keygen stores at 32
+// bytes per scalar (per fixed and permutation column the Lagrange, coefficient and extended forms,
+// and the extended l0, l_blind and l_last). It leaves out halo2's own struct overhead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct KeySizes {
+    vk: usize,
+    pk_estimate: usize,
+}
+
+// A proving key with its verifying key and the vk's fingerprint. Readers clone the Arcs out of
+// the lock and prove or verify without holding it.
+#[derive(Clone)]
+struct InstanceKeys {
+    pk: Arc<ProvingKey<Fp>>,
+    vk: Arc<VerifyingKey<Fp>>,
+    fingerprint: [u8; 32],
+}
+
+struct ZKIT {
+    storage: RwLock<HashMap<u64, CompressedData>>,
+    zkio_counter: Mutex<u64>,
+    params: Params<Fp>,
+    // Swapped whole by setup_keys and install_keys, so keys can be (re)generated through &self
+    // while other threads prove with the set they already hold.
+    keys: RwLock<Option<InstanceKeys>>,
+    hooks: RwLock<Vec<Hook>>,
+    domain: Option<String>,
+    encoding: Encoding,
+    proof_cache: RwLock<ProofCache>,
+    transcript: TranscriptKind,
+    verify_cache: Option<Mutex<VerifyCache>>,
+    randomness: Arc<dyn Randomness>,
+    proofs_started: AtomicU64,
+    pk_generations: AtomicU64,
+    commit_cost: OnceLock<Duration>,
+    codecs: RwLock<HashMap<u8, Arc<dyn Codec>>>,
+    auto_grow: bool,
+    fsync_snapshots: bool,
+    // Read-mostly mode: every write republishes a full copy of the store here, and retrieve_data
+    // loads it without touching the lock. Each write costs a clone of the whole map, and a replaced
+    // copy lives until its last reader drops it, so forgotten records linger there unzeroized.
+    published: Option<ArcSwap<HashMap<u64, CompressedData>>>,
+    access_log: Option<Mutex<AccessLog>>,
+    decode_mode: DecodeMode,
+    padding: Padding,
+    verify_mode: VerifyMode,
+    // Cached proofs are held zstd-compressed; prove still hands back the raw bytes.
+    compress_proofs: bool,
+    max_record_bytes: Option<usize>,
+    // Taken after storage and the counter; every write appends here before it is applied.
+    wal: Option<Mutex<File>>,
+    // Second-level proof cache that survives restarts; see disk_proof_path.
+    proof_dir: Option<PathBuf>,
+    // Absorbs the vk fingerprint after the domain tag, so a proof only verifies under the key it
+    // was made with. Prover and verifier must agree on it, like the domain tag; verifier packages
+    // and verify_standalone never absorb it.
+    absorb_vk: bool,
+    // Seals records in snapshots and the WAL; see ZkitBuilder::encryption_key.
+    cipher: Option<RecordCipher>,
+    // Dedicated rayon pool for proving and verifying; see in_pool.
+    pool: Option<rayon::ThreadPool>,
+    jobs: Mutex<JobState>,
+    jobs_done: Condvar,
+}
+
+// Best effort. halo2's ProvingKey and VerifyingKey keep their polynomials in private fields and
+// don't implement Zeroize, so their heap buffers are freed unwiped; dropping them first at least
+// keeps them from outliving the records. The records, which ZKIT owns outright, are wiped.
+impl Drop for ZKIT {
+    fn drop(&mut self) {
+        *self.keys.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+        let storage = self.storage.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for record in storage.values_mut() {
+            record.zeroize();
+        }
+        storage.clear();
+    }
+}
+
+impl ZKIT {
+    fn new(params: Params<Fp>) -> Self {
+        Self {
+            storage: RwLock::new(HashMap::new()),
+            zkio_counter: Mutex::new(0),
+            params,
+            keys: RwLock::new(None),
+            hooks: RwLock::new(Vec::new()),
+            domain: None,
+            encoding: Encoding::Bytewise,
+            proof_cache: RwLock::new(ProofCache::new(None)),
+            transcript: TranscriptKind::Blake2b,
+            verify_cache: None,
+            randomness: Arc::new(OsRandomness),
+            proofs_started: AtomicU64::new(0),
+            pk_generations: AtomicU64::new(0),
+            commit_cost: OnceLock::new(),
+            codecs: RwLock::new(HashMap::new()),
+            auto_grow: false,
+            fsync_snapshots: true,
+            published: None,
+            access_log: None,
+            decode_mode: DecodeMode::Strict,
+            padding: Padding::Zero,
+            verify_mode: VerifyMode::Strict,
+            compress_proofs: false,
+            max_record_bytes: None,
+            wal: None,
+            proof_dir: None,
+            absorb_vk: false,
+            cipher: None,
+            pool: None,
+            jobs: Mutex::new(JobState::default()),
+            jobs_done: Condvar::new(),
+        }
+    }
+
+    // Every proof draws from ChaCha20 keyed by the seed, on the stream numbered by the order proofs
+    // start in. A scripted sequential run is therefore reproducible; concurrent proofs still never share
+    // a stream, though which proof gets which stream depends on scheduling.
+    fn with_seed(self, seed: u64) -> Self {
+        self.with_randomness(Arc::new(SeededRandomness(seed)))
+    }
+
+    // Replaces the randomness every proof and opening draws from; OsRandomness by default.
+    fn with_randomness(mut self, randomness: Arc<dyn Randomness>) -> Self {
+        self.randomness = randomness;
+        self
+    }
+
+    fn proof_rng(&self) -> Result<Box<dyn RngCore + Send>, ZkitError> {
+        self.randomness.proof_rng(self.proofs_started.fetch_add(1, Ordering::SeqCst))
+    }
+
+    fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    // Largest degree accepted from an external SRS; beyond this keygen alone exhausts typical hosts.
+    const MAX_K: u32 = 24;
+    // Below this the domain can't hold halo2's blinding rows plus ROW_OVERHEAD, and keygen or
+    // proving fails somewhere inside halo2 instead.
+    const MIN_K: u32 = 4;
+
+    fn with_k(k: u32) -> Result<Self, ZkitError> {
+        Self::check_degree(k)?;
+        Ok(Self::new(Params::new(k)))
+    }
+
+    fn with_params_file(path: &Path) -> Result<Self, ZkitError> {
+        let mut input = io::BufReader::new(File::open(path)?);
+        let params = Params::read(&mut input)?;
+        Self::check_degree(params.k())?;
+        Ok(Self::new(params))
+    }
+
+    // Parses params straight out of a memory map of the file instead of through a read buffer.
+    // This does not keep the SRS off the heap: halo2's Params owns its generators as a Vec and
+    // has no lazily-loaded form, so they are decoded into memory either way and verification
+    // matches the in-memory path exactly. What the map saves is the copy through the reader and
+    // the page cache doing the I/O. The map is dropped once parsing finishes. Truncating or
+    // rewriting the file while it is mapped is undefined behaviour (SIGBUS at best), so only map
+    // params files nothing else writes to.
+    fn with_params_mmap(path: &Path) -> Result<Self, ZkitError> {
+        let file = File::open(path)?;
+        // SAFETY: see above; the mapping is read-only and lives only for the parse below.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let params = Params::read(&mut &map[..])?;
+        Self::check_degree(params.k())?;
+        Ok(Self::new(params))
+    }
+
+    fn check_degree(k: u32) -> Result<(), ZkitError> {
+        if k < Self::MIN_K {
+            return Err(ZkitError::DegreeTooSmall { min: Self::MIN_K });
+        }
+        if k > Self::MAX_K {
+            return Err(ZkitError::BadParams(format!("degree k={} is outside {}..={}", k, Self::MIN_K, Self::MAX_K)));
+        }
+        Ok(())
+    }
+
+    // IPA params hold no trapdoor: the generators are hashed to the curve from k alone, so the
+    // params are consistent exactly when they match a fresh Params::new(k). That rebuild costs
+    // about as much as the original setup, so this belongs at startup, not per proof.
+    fn validate_params(&self) -> Result<(), ZkitError> {
+        let k = self.params.k();
+        Self::check_degree(k)?;
+        let mut expected = Vec::new();
+        Params::<Fp>::new(k).write(&mut expected)?;
+        if self.params_bytes()? != expected {
+            return Err(ZkitError::BadParams(format!(
+                "generators don't match the canonical setup for k={}",
+                k
+            )));
+        }
+        Ok(())
+    }
+
+    // Serialized forms accepted by verify_standalone.
+    fn params_bytes(&self) -> Result<Vec<u8>, ZkitError> {
+        let mut bytes = Vec::new();
+        self.params.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn vk_bytes(&self) -> Result<Vec<u8>, ZkitError> {
+        let keys = self.instance_keys()?;
+        let vk = keys.vk.as_ref();
+        let mut bytes = Vec::new();
+        vk.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn export_verifier_package(&self, path: &Path) -> Result<(), ZkitError> {
+        let keys = self.instance_keys()?;
+        let vk = keys.vk.as_ref();
+        let mut out = io::BufWriter::new(File::create(path)?);
+        out.write_all(&PACKAGE_MAGIC)?;
+        out.write_all(&[PACKAGE_VERSION, PROOF_VERSION_TIMESTAMPED])?;
+        out.write_all(&(vk.cs().num_instance_columns() as u32).to_le_bytes())?;
+        out.write_all(&[match self.transcript {
+            TranscriptKind::Blake2b => 0,
+            TranscriptKind::Keccak => 1,
+        }])?;
+        out.write_all(&[self.absorb_vk as u8])?;
+        let tag = self.domain.as_deref().unwrap_or("");
+        out.write_all(&(tag.len() as u32).to_le_bytes())?;
+        out.write_all(tag.as_bytes())?;
+        write_package_blob(&mut out, &self.params_bytes()?)?;
+        write_package_blob(&mut out, &self.vk_bytes()?)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn save_params(&self, path: &Path) -> Result<(), ZkitError> {
+        let mut out = io::BufWriter::new(File::create(path)?);
+        self.params.write(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn builder() -> ZkitBuilder {
+        ZkitBuilder::new()
+    }
+
+    fn from_config(config: ZkitConfig) -> Result<Self, ZkitError> {
+        let mut builder = ZkitBuilder::new();
+        if let Some(k) = config.k {
+            builder = builder.k(k);
+        }
+        if let Some(encoding) = config.encoding {
+            builder = builder.encoding(encoding);
+        }
+        if let Some(kind) = config.transcript {
+            builder = builder.transcript(kind);
+        }
+        if let Some(tag) = &config.domain {
+            builder = builder.domain(tag);
+        }
+        if let Some(capacity) = config.verify_cache {
+            builder = builder.verify_cache(capacity);
+        }
+        if let Some(enabled) = config.fsync_snapshots {
+            builder = builder.fsync_snapshots(enabled);
+        }
+        if let Some(mode) = config.decode_mode {
+            builder = builder.decode_mode(mode);
+        }
+        if let Some(padding) = config.padding {
+            builder = builder.padding(padding);
+        }
+        if let Some(mode) = config.verify_mode {
+            builder = builder.verify_mode(mode);
+        }
+        let zkit = builder.build()?;
+        if let Some(path) = &config.snapshot_path {
+            if path.exists() {
+                zkit.restore(path)?;
+            }
+        }
+        Ok(zkit)
+    }
+
+    // halo2 reserves blinding_factors() + 1 rows at the end of the domain (six for these circuits),
+    // and we keep a few more spare so gates using Rotation::prev/next never run off the usable region.
+    const ROW_OVERHEAD: usize = 10;
+
+    // Stops one past MAX_K when even that domain is too small, which check_degree then rejects.
+    fn auto_k(max_record_len: usize) -> u32 {
+        let rows = max_record_len.saturating_add(Self::ROW_OVERHEAD);
+        let mut k = Self::MIN_K;
+        while k <= Self::MAX_K && (1usize << k) < rows {
+            k += 1;
+        }
+        k
+    }
+
+    fn for_record_len(max_record_len: usize) -> Result<Self, ZkitError> {
+        Self::with_k(Self::auto_k(max_record_len))
+    }
+
+    // How far a single auto_grow retry raises k (4x the rows), still capped at MAX_K.
+    const AUTO_GROW_STEP: u32 = 2;
+
+    // Like create_proof, but with auto_grow set a circuit that doesn't fit the current params is
+    // retried once with larger params and fresh keys. Proofs cached under the old keys need reprove_all.
+    fn create_proof_growing(&mut self, circuit: &(impl Circuit<Fp> + Sync)) -> Result<Vec<u8>, ZkitError> {
+        match self.create_proof(circuit) {
+            Err(ZkitError::Halo2(Error::NotEnoughRowsAvailable { .. }))
+                if self.auto_grow && self.params.k() < Self::MAX_K =>
+            {
+                let from = self.params.k();
+                let to = (from + Self::AUTO_GROW_STEP).min(Self::MAX_K);
+                self.params = Params::new(to);
+                self.setup_keys(circuit, false)?;
+                self.emit(ZkitEvent::ParamsGrown { from, to });
+                self.create_proof(circuit)
+            }
+            result => result,
+        }
+    }
+
+    fn log_access(&self, kind: AccessKind, id: Option<u64>) {
+        if let Some(log) = &self.access_log {
+            log.lock().unwrap().push(AccessEntry {
+                kind,
+                id,
+                timestamp: unix_now(),
+            });
+        }
+    }
+
+    // Oldest first; empty unless the builder enabled the log.
+    fn access_log(&self) -> Vec<AccessEntry> {
+        self.access_log
+            .as_ref()
+            .map_or_else(Vec::new, |log| log.lock().unwrap().entries.iter().cloned().collect())
+    }
+
+    fn on_event(&self, hook: impl Fn(&ZkitEvent) + Send + Sync + 'static) {
+        self.hooks.write().unwrap().push(Box::new(hook));
+    }
+
+    fn emit(&self, event: ZkitEvent) {
+        for hook in self.hooks.read().unwrap().iter() {
+            hook(&event);
+        }
+    }
+
+    // Keeps the current keys when the circuit's vk fingerprint matches theirs, unless `force`. The
+    // vk is still generated to compare, since it is the only fingerprint that covers fixed
+    // columns such as lookup tables; the proving key, which costs far more, is what gets skipped.
+    // Keygen runs without the keys lock held, so proofs already running keep their keys; two
+    // concurrent calls both generate, and the last to finish wins.
+    fn setup_keys(&self, circuit: &impl Circuit<Fp>, force: bool) -> Result<(), Error> {
+        // Keygen is deterministic apart from I/O, so only I/O failures are worth retrying.
+        let transient = |e: &Error| matches!(e, Error::Transcript(_));
+        let vk = with_retry(|| keygen_vk(&self.params, circuit), transient)?;
+        let fingerprint = fingerprint_vk(&vk);
+        if !force && self.vk_fingerprint() == Some(fingerprint) {
+            return Ok(());
+        }
+        let pk = with_retry(|| keygen_pk(&self.params, vk.clone(), circuit), transient)?;
+        self.pk_generations.fetch_add(1, Ordering::Relaxed);
+        *self.keys.write().unwrap() = Some(InstanceKeys {
+            pk: Arc::new(pk),
+            vk: Arc::new(vk),
+            fingerprint,
+        });
+        Ok(())
+    }
+
+    // How many proving keys setup_keys has generated on this instance.
+    fn pk_generations(&self) -> u64 {
+        self.pk_generations.load(Ordering::Relaxed)
+    }
+
+    fn instance_keys(&self) -> Result<InstanceKeys, ZkitError> {
+        self.keys.read().unwrap().clone().ok_or(ZkitError::KeysNotSetUp)
+    }
+
+    // Rotates the instance keys. Cached proofs were made under the old keys and are kept only so
+    // reprove_all knows which (record, inputs) pairs to refresh; audit reports them as stale.
+    fn regenerate_keys(&self, circuit: &impl Circuit<Fp>) -> Result<(), ZkitError> {
+        self.setup_keys(circuit, true)?;
+        Ok(())
+    }
+
+    // For keys produced elsewhere. With `check`, the pair is run through check_key_pair and
+    // the previous keys are restored if it fails.
+    fn install_keys(&self, pk: ProvingKey<Fp>, vk: VerifyingKey<Fp>, check: bool) -> Result<(), ZkitError> {
+        let keys = InstanceKeys {
+            fingerprint: fingerprint_vk(&vk),
+            pk: Arc::new(pk),
+            vk: Arc::new(vk),
+        };
+        let previous = self.keys.write().unwrap().replace(keys);
+        if check {
+            if let Err(e) = self.check_key_pair() {
+                *self.keys.write().unwrap() = previous;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    // Proves and verifies a one-element zero witness, which satisfies the example gate, to confirm
+    // pk and vk belong together. Keys generated for a lookup circuit need that table to prove, so
+    // they fail here even when paired correctly.
+    fn check_key_pair(&self) -> Result<(), ZkitError> {
+        let circuit: ExampleCircuit<Fp> = ExampleCircuit {
+            data: vec![Fp::zero()],
+            lookup: None,
+            _marker: PhantomData,
+        };
+        let proof = self.create_proof(&circuit)?;
+        if !self.verify_proof(&proof)? {
+            return Err(ZkitError::VkMismatch);
+        }
+        Ok(())
+    }
+
+    fn vk_fingerprint(&self) -> Option<[u8; 32]> {
+        self.keys.read().unwrap().as_ref().map(|keys| keys.fingerprint)
+    }
+
+    // The halo2 keys themselves, for calling halo2 directly. None until keys are set up.
+    fn proving_key(&self) -> Option<Arc<ProvingKey<Fp>>> {
+        self.instance_keys().ok().map(|keys| keys.pk)
+    }
+
+    fn verifying_key(&self) -> Option<Arc<VerifyingKey<Fp>>> {
+        self.instance_keys().ok().map(|keys| keys.vk)
+    }
+
+    // None until keys are set up; see KeySizes for what each figure means.
+    fn key_sizes(&self) -> Option<KeySizes> {
+        let keys = self.instance_keys().ok()?;
+        let vk = keys.vk.as_ref();
+        let vk_size = self.vk_bytes().ok()?.len();
+        let domain = vk.get_domain();
+        let n = 1usize << self.params.k();
+        let per_column = (2 * n + domain.extended_len()) * 32;
+        let columns = vk.cs().num_fixed_columns() + vk.cs().permutation().get_columns().len();
+        let pk_estimate = vk_size + 3 * domain.extended_len() * 32 + columns * per_column;
+        Some(KeySizes { vk: vk_size, pk_estimate })
+    }
+
+    // Keygen is deterministic for a given circuit and params, so deployments can pin the
+    // fingerprint and catch a code change that altered the circuit at startup.
+    fn assert_vk_fingerprint(&self, expected: [u8; 32]) -> Result<(), ZkitError> {
+        match self.vk_fingerprint() {
+            None => Err(ZkitError::KeysNotSetUp),
+            Some(actual) if actual != expected => Err(ZkitError::VkMismatch),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn check_record_size(&self, len: u64) -> Result<(), ZkitError> {
+        match self.max_record_bytes {
+            Some(limit) if len > limit as u64 => Err(ZkitError::RecordTooLarge { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    fn batch_and_inscribe(&self, data: Vec<u8>) -> Result<RecordId, ZkitError> {
+        if self.encoding == Encoding::Delta {
+            return self.inscribe_delta(data).map(RecordId);
+        }
+        self.check_record_size(data.len() as u64)?;
+        let codec = self.codec(self.encoding)?;
+        let compressed_data = ingest_and_compress(data, codec.as_ref(), self.padding);
+        self.insert_next(compressed_data).map(RecordId)
+    }
+
+    // Stores record N as its bytes minus record N-1's, element by element, so a record that
+    // barely differs from the one before is mostly zeros. Without a readable N-1 the deltas are
+    // from nothing. The statement proofs see the stored deltas, not the bytes they stand for.
+    fn inscribe_delta(&self, data: Vec<u8>) -> Result<u64, ZkitError> {
+        self.check_record_size(data.len() as u64)?;
+        let stream = self.padding.apply(&data, 1);
+        let id = {
+            let mut storage = self.storage.write().unwrap();
+            let mut zkio_counter = self.zkio_counter.lock().unwrap();
+            let id = zkio_counter.checked_add(1).ok_or(ZkitError::IdsExhausted)?;
+            let base = storage.get(&(id - 1)).and_then(|base| self.decode_record(base, &storage).ok());
+            let mut elements = vec![Fp::from(if base.is_some() { id - 1 } else { 0 })];
+            let base = base.unwrap_or_default();
+            elements.extend(stream.iter().enumerate().map(|(idx, &byte)| {
+                Fp::from(byte as u64) - Fp::from(base.get(idx).copied().unwrap_or(0) as u64)
+            }));
+            let mut record = CompressedData::new(elements, Encoding::Delta, data.len());
+            record.padding = self.padding;
+            self.wal_put(id, &record, id)?;
+            *zkio_counter = id;
+            storage.insert(id, record);
+            self.publish(&storage);
+            id
+        };
+        self.emit(ZkitEvent::Ingested(id));
+        Ok(id)
+    }
+
+    // Stores a record under the next counter value.
+    fn insert_next(&self, record: CompressedData) -> Result<u64, ZkitError> {
+        let id = {
+            let mut storage = self.storage.write().unwrap();
+            let mut zkio_counter = self.zkio_counter.lock().unwrap();
+            // The counter can be set to anything by insert_with_id or a restored snapshot.
+            let id = zkio_counter.checked_add(1).ok_or(ZkitError::IdsExhausted)?;
+            self.wal_put(id, &record, id)?;
+            *zkio_counter = id;
+            storage.insert(id, record);
+            self.publish(&storage);
+            id
+        };
+        // Hooks run after the locks are released so they can read the store.
+        self.emit(ZkitEvent::Ingested(id));
+        Ok(id)
+    }
+
+    // Stores a record under a caller-chosen id, e.g. one replicated from another instance. The
+    // counter is moved past it so later ingests don't land on it.
+    fn insert_with_id(&self, id: RecordId, data: Vec<u8>) -> Result<(), ZkitError> {
+        let RecordId(id) = id;
+        self.check_record_size(data.len() as u64)?;
+        let codec = self.codec(self.encoding)?;
+        let record = ingest_and_compress(data, codec.as_ref(), self.padding);
+        {
+            let mut storage = self.storage.write().unwrap();
+            let mut zkio_counter = self.zkio_counter.lock().unwrap();
+            if storage.contains_key(&id) {
+                return Err(ZkitError::IdInUse(id));
+            }
+            let counter = (*zkio_counter).max(id);
+            self.wal_put(id, &record, counter)?;
+            *zkio_counter = counter;
+            storage.insert(id, record);
+            self.publish(&storage);
+        }
+        self.emit(ZkitEvent::Ingested(id));
+        Ok(())
+    }
+
+    // Stores field elements as they are, skipping the codec. The record is marked Packed with a
+    // byte length of 31 per element, so an element of 2^248 or more makes a strict retrieve_data
+    // fail with ValueOutOfRange and a lenient one drop its top byte. retrieve_field always gives
+    // the elements back unchanged.
+    fn inscribe_field(&self, data: Vec<Fp>) -> Result<RecordId, ZkitError> {
+        let orig_len = data.len() * PACKED_CHUNK;
+        self.check_record_size(orig_len as u64)?;
+        self.insert_next(CompressedData::new(data, Encoding::Packed, orig_len)).map(RecordId)
+    }
+
+    fn retrieve_field(&self, id: RecordId) -> Result<Vec<Fp>, ZkitError> {
+        let RecordId(id) = id;
+        self.log_access(AccessKind::Retrieve, Some(id));
+        Ok(self.record(id)?.data)
+    }
+
+    // Blake2b of the raw bytes, so the id is the same whatever encoding an instance prefers.
+    fn deterministic_id(data: &[u8]) -> u64 {
+        let hash = blake2b_simd::Params::new()
+            .hash_length(8)
+            .personal(b"ZKIT_Determ_Id__")
+            .hash(data);
+        u64::from_le_bytes(hash.as_bytes().try_into().unwrap())
+    }
+
+    // Stores data under deterministic_id instead of the next counter value. Inscribing the same
+    // bytes again returns the existing id. If the id already holds different bytes (a 64-bit hash
+    // collision, or a counter-assigned record that happens to sit there) nothing is overwritten
+    // and IdInUse is returned.
+    fn inscribe_deterministic(&self, data: Vec<u8>) -> Result<RecordId, ZkitError> {
+        self.check_record_size(data.len() as u64)?;
+        let id = Self::deterministic_id(&data);
+        let codec = self.codec(self.encoding)?;
+        {
+            let mut storage = self.storage.write().unwrap();
+            if let Some(existing) = storage.get(&id) {
+                return if self.decode_record(existing, &storage)? == data {
+                    Ok(RecordId(id))
+                } else {
+                    Err(ZkitError::IdInUse(id))
+                };
+            }
+            let record = ingest_and_compress(data, codec.as_ref(), self.padding);
+            self.wal_put(id, &record, *self.zkio_counter.lock().unwrap())?;
+            storage.insert(id, record);
+            self.publish(&storage);
+        }
+        self.emit(ZkitEvent::Ingested(id));
+        Ok(RecordId(id))
+    }
+
+    fn ingest_file(&self, path: &Path) -> Result<RecordId, ZkitError> {
+        // Checked against the file size first so an oversized file is never read in.
+        self.check_record_size(fs::metadata(path)?.len())?;
+        let data = fs::read(path)?;
+        self.batch_and_inscribe(data)
+    }
+
+    fn ingest_dir(&self, dir: &Path) -> Result<Vec<(PathBuf, RecordId)>, ZkitError> {
+        self.ingest_dir_with(dir, false)
+    }
+
+    // Files that can't be read are skipped and reported through an IngestSkipped event.
+    // Paths are ingested in sorted order so ids are reproducible for the same tree.
+    fn ingest_dir_with(&self, dir: &Path, recursive: bool) -> Result<Vec<(PathBuf, RecordId)>, ZkitError> {
+        let mut paths = Vec::new();
+        collect_files(dir, recursive, &mut paths)?;
+        paths.sort();
+
+        let mut ingested = Vec::new();
+        for path in paths {
+            match self.ingest_file(&path) {
+                Ok(id) => ingested.push((path, id)),
+                Err(e) => self.emit(ZkitEvent::IngestSkipped {
+                    path,
+                    reason: e.to_string(),
+                }),
+            }
+        }
+        Ok(ingested)
+    }
+
+    // Only a hint under concurrency: another thread may ingest between the peek and the caller's own
+    // batch_and_inscribe, in which case the caller gets a later id than the one peeked.
+    fn peek_next_id(&self) -> u64 {
+        self.zkio_counter.lock().unwrap().saturating_add(1)
+    }
+
+    fn create_proof(&self, circuit: &(impl Circuit<Fp> + Sync)) -> Result<Vec<u8>, ZkitError> {
+        self.create_proof_with_inputs(circuit, &[])
+    }
+
+    fn spawn_proof(self: &Arc<Self>, circuit: ExampleCircuit<Fp>) -> ProofTask {
+        let (tx, rx) = mpsc::channel();
+        match self.begin_job() {
+            Ok(job) => {
+                thread::spawn(move || {
+                    let _ = tx.send(job.0.create_proof(&circuit));
+                });
+            }
+            Err(e) => {
+                let _ = tx.send(Err(e));
+            }
+        }
+        ProofTask { rx }
+    }
+
+    // spawn_proof for a stored record through prove, so the proof lands in the proof cache.
+    fn spawn_record_proof(self: &Arc<Self>, id: u64) -> ProofTask {
+        let (tx, rx) = mpsc::channel();
+        match self.begin_job() {
+            Ok(job) => {
+                thread::spawn(move || {
+                    let _ = tx.send(job.0.prove(id, &[]));
+                });
+            }
+            Err(e) => {
+                let _ = tx.send(Err(e));
+            }
+        }
+        ProofTask { rx }
+    }
+
+    fn begin_job(self: &Arc<Self>) -> Result<JobGuard, ZkitError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.closed {
+            return Err(ZkitError::ShuttingDown);
+        }
+        jobs.running += 1;
+        Ok(JobGuard(Arc::clone(self)))
+    }
+
+    // Refuses new threaded jobs, waits up to timeout for running ones and, if they all finished,
+    // writes a snapshot when a path is given. Workers hold their own handles, so on a timeout they
+    // keep running to completion after this returns.
+    fn shutdown(self: Arc<Self>, timeout: Duration, snapshot: Option<&Path>) -> Result<(), ZkitError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.closed = true;
+        let (jobs, wait) = self.jobs_done.wait_timeout_while(jobs, timeout, |jobs| jobs.running > 0).unwrap();
+        if wait.timed_out() {
+            return Err(ZkitError::ShutdownTimeout { in_flight: jobs.running });
+        }
+        drop(jobs);
+        if let Some(path) = snapshot {
+            self.snapshot(path)?;
+        }
+        Ok(())
+    }
+
+    // Verifies (job id, proof) pairs as they arrive, on one worker per core. Results come back
+    // tagged with the job id, in completion order. Workers exit once the input channel closes and
+    // is drained, which also closes the returned channel.
+    fn verify_stream(
+        self: &Arc<Self>,
+        rx: mpsc::Receiver<(u64, Vec<u8>)>,
+    ) -> mpsc::Receiver<(u64, Result<bool, ZkitError>)> {
+        let (tx, results) = mpsc::channel();
+        let jobs = Arc::new(Mutex::new(rx));
+        let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        for _ in 0..workers {
+            let zkit = Arc::clone(self);
+            let jobs = Arc::clone(&jobs);
+            let tx = tx.clone();
+            thread::spawn(move || loop {
+                // Hold the lock only to take a job, not while verifying it.
+                let job = jobs.lock().unwrap().recv();
+                let Ok((job_id, proof)) = job else { break };
+                let result = zkit.begin_job().and_then(|_job| zkit.verify_proof(&proof));
+                if tx.send((job_id, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        results
+    }
+
+    fn create_proof_with_inputs(
+        &self,
+        circuit: &(impl Circuit<Fp> + Sync),
+        public_inputs: &[Fp],
+    ) -> Result<Vec<u8>, ZkitError> {
+        self.create_proof_bound(circuit, public_inputs, &TranscriptBinding::default())
+    }
+
+    // The seed is absorbed before anything else in the transcript; verify_proof_with_seed needs the
+    // same one. None proves exactly like create_proof_with_inputs.
+    fn create_proof_with_seed(
+        &self,
+        circuit: &(impl Circuit<Fp> + Sync),
+        public_inputs: &[Fp],
+        seed: Option<&[u8]>,
+    ) -> Result<Vec<u8>, ZkitError> {
+        let binding = TranscriptBinding {
+            timestamp: None,
+            seed: seed.map(<[u8]>::to_vec),
+        };
+        self.create_proof_bound(circuit, public_inputs, &binding)
+    }
+
+    // Returns a v3 frame; the timestamp is bound into the transcript as well as the header.
+    fn create_proof_timestamped(
+        &self,
+        circuit: &(impl Circuit<Fp> + Sync),
+        public_inputs: &[Fp],
+        timestamp: u64,
+    ) -> Result<Vec<u8>, ZkitError> {
+        self.create_proof_timestamped_with_seed(circuit, public_inputs, timestamp, None)
+    }
+
+    // Both bindings at once, for a VerifyPolicy with a max_age and a context. The seed, as with
+    // create_proof_with_seed, is not in the frame: verify_proof_with_seed supplies it.
+    fn create_proof_timestamped_with_seed(
+        &self,
+        circuit: &(impl Circuit<Fp> + Sync),
+        public_inputs: &[Fp],
+        timestamp: u64,
+        seed: Option<&[u8]>,
+    ) -> Result<Vec<u8>, ZkitError> {
+        let binding = TranscriptBinding {
+            timestamp: Some(timestamp),
+            seed: seed.map(<[u8]>::to_vec),
+        };
+        let transcript = self.create_proof_bound(circuit, public_inputs, &binding)?;
+        Ok(write_timestamped_proof(self.params.k(), timestamp, &transcript))
+    }
+
+    fn create_proof_bound(
+        &self,
+        circuit: &(impl Circuit<Fp> + Sync),
+        public_inputs: &[Fp],
+        binding: &TranscriptBinding,
+    ) -> Result<Vec<u8>, ZkitError> {
+        let keys = self.instance_keys()?;
+        let pk = keys.pk.as_ref();
+        let columns = route_public_inputs(public_inputs, pk.get_vk().cs().num_instance_columns());
+        let instances: Vec<&[Fp]> = columns.iter().map(Vec::as_slice).collect();
+        let result = self.prove_with(pk, circuit, &instances, binding);
+        self.emit(ZkitEvent::ProofCreated { ok: result.is_ok() });
+        result
+    }
+
+    fn create_artifact(
+        &self,
+        circuit: &(impl Circuit<Fp> + Sync),
+        public_inputs: &[Fp],
+    ) -> Result<ProofArtifact, ZkitError> {
+        let proof = self.create_proof_with_inputs(circuit, public_inputs)?;
+        Ok(ProofArtifact {
+            proof,
+            public_inputs: public_inputs.to_vec(),
+            vk_fingerprint: self.instance_keys()?.fingerprint.to_vec(),
+            params_k: self.params.k(),
+            created_at: unix_now(),
+        })
+    }
+
+    fn verify_artifact(&self, artifact: &ProofArtifact) -> Result<bool, ZkitError> {
+        let fingerprint = self.instance_keys()?.fingerprint;
+        if artifact.vk_fingerprint != fingerprint {
+            return Err(ZkitError::VkMismatch);
+        }
+        if artifact.params_k != self.params.k() {
+            return Err(ZkitError::KMismatch {
+                proof_k: artifact.params_k,
+                params_k: self.params.k(),
+            });
+        }
+        self.verify_proof_with_inputs(&artifact.proof, &artifact.public_inputs)
+    }
+
+    fn verify_proof(&self, proof: &[u8]) -> Result<bool, ZkitError> {
+        self.verify_proof_with_inputs(proof, &[])
+    }
+
+    // Accepts a JSON ProofArtifact, whose embedded public inputs are used, or a bare proof, which
+    // carries none and is checked against `expected` (or no inputs). With `expected`, an artifact
+    // whose inputs differ is reported as a diff without running the crypto check. k is the
+    // artifact's or frame's, or the params' for an unframed proof.
+    fn verify_verbose(&self, proof: &[u8], expected: Option<&[Fp]>) -> Result<VerifyOutcome, ZkitError> {
+        if let Ok(artifact) = serde_json::from_slice::<ProofArtifact>(proof) {
+            if let Some(diff) = expected.and_then(|expected| PublicInputDiff::between(&artifact.public_inputs, expected)) {
+                return Ok(VerifyOutcome {
+                    ok: false,
+                    k: artifact.params_k,
+                    public_inputs: artifact.public_inputs,
+                    diff: Some(diff),
+                });
+            }
+            return Ok(VerifyOutcome {
+                ok: self.verify_artifact(&artifact)?,
+                k: artifact.params_k,
+                public_inputs: artifact.public_inputs,
+                diff: None,
+            });
+        }
+        let expanded = expand_proof(proof)?;
+        let k = proof_info(&expanded)?.k.unwrap_or(self.params.k());
+        let public_inputs = expected.unwrap_or_default().to_vec();
+        Ok(VerifyOutcome {
+            ok: self.verify_proof_with_inputs(&expanded, &public_inputs)?,
+            public_inputs,
+            k,
+            diff: None,
+        })
+    }
+
+    fn verify_proof_with_inputs(&self, proof: &[u8], public_inputs: &[Fp]) -> Result<bool, ZkitError> {
+        self.verify_proof_with_mode(proof, public_inputs, self.verify_mode)
+    }
+
+    // Safe mode for a stored record: after the proof verifies, the example circuit is re-run under
+    // MockProver with the record as witness and the same public inputs. That catches keys that
+    // verify proofs the intended constraints would reject. MockProver needs the witness, so this
+    // only works where the record is held, and it costs about as much as proving again.
+    fn verify_proof_double_checked(&self, id: u64, proof: &[u8], public_inputs: &[Fp]) -> Result<bool, ZkitError> {
+        if !self.verify_proof_with_inputs(proof, public_inputs)? {
+            return Ok(false);
+        }
+        let circuit: ExampleCircuit<Fp> = ExampleCircuit {
+            data: self.record(id)?.data,
+            lookup: None,
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(self.params.k(), &circuit, vec![public_inputs.to_vec()])?;
+        if let Err(failures) = prover.verify() {
+            let first = failures.first().map(|f| f.to_string()).unwrap_or_default();
+            return Err(ZkitError::MockCheckFailed(format!("{} failures, first: {}", failures.len(), first)));
+        }
+        Ok(true)
+    }
+
+    // verify_proof_with_inputs with every transcript operation (preamble included) reported to
+    // `observe` as it happens. The answer is the same; the verify cache and the pool are bypassed
+    // so each call really walks the transcript, on the calling thread.
+    fn verify_proof_interactive(
+        &self,
+        proof: &[u8],
+        public_inputs: &[Fp],
+        observe: impl FnMut(TranscriptStep),
+    ) -> Result<bool, ZkitError> {
+        self.log_access(AccessKind::Verify, None);
+        let decoded = self.decode_proof(proof, public_inputs, self.verify_mode)?;
+        let keys = self.instance_keys()?;
+        let vk = keys.vk.as_ref();
+        let instances: Vec<&[Fp]> = decoded.instances.iter().map(Vec::as_slice).collect();
+        let domain = self.domain.as_deref();
+        let vk_fingerprint = self.absorb_vk.then(|| fingerprint_vk(vk));
+
+        let mut rest = decoded.transcript.as_slice();
+        let result = match self.transcript {
+            TranscriptKind::Blake2b => {
+                let mut transcript = ObservedTranscript {
+                    inner: Blake2bRead::<_, _, Challenge255<_>>::init(&mut rest),
+                    observe,
+                };
+                absorb_preamble(domain, vk_fingerprint.as_ref(), &decoded.binding, &mut transcript)
+                    .and_then(|()| verify_halo2(&self.params, vk, &instances, &mut transcript))
+            }
+            TranscriptKind::Keccak => {
+                let mut transcript = ObservedTranscript {
+                    inner: KeccakRead::init(&mut rest),
+                    observe,
+                };
+                absorb_preamble(domain, vk_fingerprint.as_ref(), &decoded.binding, &mut transcript)
+                    .and_then(|()| verify_halo2(&self.params, vk, &instances, &mut transcript))
+            }
+        };
+        let result = result.and_then(|ok| {
+            if decoded.mode == VerifyMode::Strict && !rest.is_empty() {
+                return Err(ZkitError::MalformedProof);
+            }
+            Ok(ok)
+        });
+        self.emit(ZkitEvent::ProofVerified { ok: matches!(result, Ok(true)) });
+        result
+    }
+
+    // Bypasses the verify cache, whose key doesn't cover the seed.
+    fn verify_proof_with_seed(&self, proof: &[u8], public_inputs: &[Fp], seed: Option<&[u8]>) -> Result<bool, ZkitError> {
+        self.log_access(AccessKind::Verify, None);
+        let mut decoded = self.decode_proof(proof, public_inputs, self.verify_mode)?;
+        decoded.binding.seed = seed.map(<[u8]>::to_vec);
+        let result = self.verify_stage(&decoded);
+        self.emit(ZkitEvent::ProofVerified { ok: matches!(result, Ok(true)) });
+        result
+    }
+
+    // Overrides the instance's verify mode for one call.
+    fn verify_proof_with_mode(&self, proof: &[u8], public_inputs: &[Fp], mode: VerifyMode) -> Result<bool, ZkitError> {
+        self.log_access(AccessKind::Verify, None);
+        let decoded = self.decode_proof(proof, public_inputs, mode)?;
+        // Keyed on the whole input so a forged header over a cached transcript isn't a hit.
+        let cache_key = self.verify_cache.as_ref().map(|_| {
+            verify_cache_key(&self.vk_fingerprint().unwrap_or_default(), public_inputs, proof, mode)
+        });
+        if let (Some(cache), Some(key)) = (&self.verify_cache, &cache_key) {
+            let hit = cache.lock().unwrap().get(key);
+            if let Some(ok) = hit {
+                // Hooks see a cached answer as a verification like any other.
+                self.emit(ZkitEvent::ProofVerified { ok });
+                return Ok(ok);
+            }
+        }
+
+        let result = self.verify_stage(&decoded);
+        if let (Some(cache), Some(key), Ok(ok)) = (&self.verify_cache, cache_key, &result) {
+            cache.lock().unwrap().insert(key, *ok);
+        }
+        self.emit(ZkitEvent::ProofVerified { ok: matches!(result, Ok(true)) });
+        result
+    }
+
+    fn sign_proof(&self, proof: &[u8], signing_key: &ed25519_dalek::SigningKey) -> SignedProof {
+        use ed25519_dalek::Signer;
+        SignedProof {
+            proof: proof.to_vec(),
+            signature: signing_key.sign(&signed_proof_message(proof)).to_bytes(),
+        }
+    }
+
+    // The signature is checked first, so a proof from the wrong author is rejected with
+    // BadSignature without running the verifier.
+    fn verify_signed_proof(&self, signed: &SignedProof, public_key: &VerifyingKeyBytes) -> Result<bool, ZkitError> {
+        let key = ed25519_dalek::VerifyingKey::from_bytes(public_key).map_err(|_| ZkitError::BadSignature)?;
+        let signature = ed25519_dalek::Signature::from_bytes(&signed.signature);
+        key.verify_strict(&signed_proof_message(&signed.proof), &signature)
+            .map_err(|_| ZkitError::BadSignature)?;
+        self.verify_proof(&signed.proof)
+    }
+
+    // Runs every check the policy enables, even after one fails, so the result says everything
+    // that is wrong with the proof. The cheap checks go first. `signature` is only looked at
+    // when the policy names a signer.
+    fn verify_with_policy(
+        &self,
+        proof: &[u8],
+        public_inputs: &[Fp],
+        signature: Option<&[u8; 64]>,
+        policy: &VerifyPolicy,
+    ) -> PolicyResult {
+        let mut checks = Vec::new();
+        if let Some(expected) = policy.vk_fingerprint {
+            checks.push((PolicyCheck::VkPinned, self.assert_vk_fingerprint(expected).map(|()| true)));
+        }
+        if let Some(public_key) = &policy.signer {
+            let outcome = signature.ok_or(ZkitError::BadSignature).and_then(|signature| {
+                let key = ed25519_dalek::VerifyingKey::from_bytes(public_key).map_err(|_| ZkitError::BadSignature)?;
+                key.verify_strict(&signed_proof_message(proof), &ed25519_dalek::Signature::from_bytes(signature))
+                    .map_err(|_| ZkitError::BadSignature)?;
+                Ok(true)
+            });
+            checks.push((PolicyCheck::Signed, outcome));
+        }
+        if let Some(max_age) = policy.max_age {
+            let max_age = max_age.as_secs();
+            let outcome = proof_info(proof).and_then(|info| {
+                let age = info.timestamp.map_or(u64::MAX, |timestamp| unix_now().saturating_sub(timestamp));
+                if age > max_age {
+                    return Err(ZkitError::Expired { age, max_age });
+                }
+                Ok(true)
+            });
+            checks.push((PolicyCheck::Fresh, outcome));
+        }
+        let valid = match &policy.context {
+            Some(context) => self.verify_proof_with_seed(proof, public_inputs, Some(context)),
+            None => self.verify_proof_with_inputs(proof, public_inputs),
+        };
+        checks.push((PolicyCheck::Valid, valid));
+        PolicyResult { checks }
+    }
+
+    // The cheap half of verify_proof: decompression, framing, length and arity checks and input
+    // routing, with no curve arithmetic. Stages can run on separate pools, many decodes feeding
+    // fewer verify_stage workers. halo2 decodes the transcript's points inside its own verifier,
+    // so those are still parsed in verify_stage.
+    fn decode_stage(&self, proof: &[u8], public_inputs: &[Fp]) -> Result<DecodedProof, ZkitError> {
+        self.decode_proof(proof, public_inputs, self.verify_mode)
+    }
+
+    fn decode_proof(&self, proof: &[u8], public_inputs: &[Fp], mode: VerifyMode) -> Result<DecodedProof, ZkitError> {
+        let expanded = expand_proof(proof)?;
+        // Every proof carries at least one compressed point, so anything shorter is a user mistake.
+        if expanded.len() < MIN_PROOF_LEN {
+            return Err(ZkitError::EmptyProof);
+        }
+        let keys = self.instance_keys()?;
+        let vk = keys.vk.as_ref();
+        // halo2 only fails obscurely on a proof from a different k, so a framed proof's k is
+        // compared up front. Raw proofs carry no k.
+        if expanded.starts_with(&PROOF_MAGIC) {
+            let proof_k = peek_proof_header(&expanded)?.k;
+            if proof_k != self.params.k() {
+                return Err(ZkitError::KMismatch { proof_k, params_k: self.params.k() });
+            }
+        }
+        let (transcript, binding) = proof_transcript(&expanded)?;
+        let arity = vk.cs().num_instance_columns();
+        if arity > 1 && public_inputs.len() % arity != 0 {
+            return Err(ZkitError::ArityMismatch { columns: arity, inputs: public_inputs.len() });
+        }
+        Ok(DecodedProof {
+            transcript,
+            binding,
+            instances: route_public_inputs(public_inputs, arity),
+            mode,
+        })
+    }
+
+    // verify_proof for a proof of record id, split into its two stages and timed. Like verify_proof
+    // it takes no public inputs, and it skips the verify cache so the timings are real.
+    fn verify_proof_timed_breakdown(&self, id: u64, proof: &[u8]) -> Result<(bool, VerifyTimings), ZkitError> {
+        let start = Instant::now();
+        self.log_access(AccessKind::Verify, Some(id));
+        let decoded = self.decode_stage(proof, &[])?;
+        let decode = start.elapsed();
+        let verify_start = Instant::now();
+        let result = self.verify_stage(&decoded);
+        let verify = verify_start.elapsed();
+        self.emit(ZkitEvent::ProofVerified { ok: matches!(result, Ok(true)) });
+        let ok = result?;
+        Ok((ok, VerifyTimings { decode, verify, total: start.elapsed() }))
+    }
+
+    // The expensive half: the halo2 verifier and its MSM. Gives the same answer verify_proof would.
+    fn verify_stage(&self, decoded: &DecodedProof) -> Result<bool, ZkitError> {
+        let keys = self.instance_keys()?;
+        let vk = keys.vk.as_ref();
+        let instances: Vec<&[Fp]> = decoded.instances.iter().map(Vec::as_slice).collect();
+        self.verify_with(vk, &instances, &decoded.transcript, &decoded.binding, decoded.mode)
+    }
+
+    // Checks all proofs with one accumulated MSM instead of one per proof. halo2's batch verifier
+    // only knows the bare Blake2b transcript, so proofs with a domain tag, a timestamp, an absorbed
+    // vk or the Keccak transcript are verified individually after the batch. It also can't tell
+    // where a transcript ends, so under VerifyMode::Strict, which rejects trailing bytes, every
+    // proof is verified individually too. One bad proof fails the whole call; first_invalid finds which.
+    fn verify_batch(&self, items: &[(Vec<Fp>, Vec<u8>)]) -> Result<bool, ZkitError> {
+        let keys = self.instance_keys()?;
+        let vk = keys.vk.as_ref();
+        let mut batch = BatchVerifier::new();
+        let mut individual = Vec::new();
+        for (inputs, proof) in items {
+            // Decompresses and runs the k and arity checks verify_proof would.
+            let decoded = self.decode_stage(proof, inputs)?;
+            let batchable = self.transcript == TranscriptKind::Blake2b
+                && decoded.mode == VerifyMode::Relaxed
+                && self.domain.is_none()
+                && !self.absorb_vk
+                && decoded.binding == TranscriptBinding::default();
+            if batchable {
+                batch.add_proof(vec![decoded.instances], decoded.transcript);
+            } else {
+                individual.push((inputs, proof));
+            }
+        }
+        let ok = panic::catch_unwind(AssertUnwindSafe(|| batch.finalize(&self.params, vk)))
+            .map_err(|_| ZkitError::MalformedProof)?;
+        self.emit(ZkitEvent::ProofVerified { ok });
+        if !ok {
+            return Ok(false);
+        }
+        for (inputs, proof) in individual {
+            if !self.verify_proof_with_inputs(proof, inputs)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Index of the first proof that fails on its own, or None when all of them verify.
+    fn first_invalid(&self, items: &[(Vec<Fp>, Vec<u8>)]) -> Result<Option<usize>, ZkitError> {
+        for (idx, (inputs, proof)) in items.iter().enumerate() {
+            if !self.verify_proof_with_inputs(proof, inputs)? {
+                return Ok(Some(idx));
+            }
+        }
+        Ok(None)
+    }
+
+    // Untimestamped proofs have no age to check and are rejected the same way as stale ones.
+    fn verify_proof_fresh(
+        &self,
+        proof: &[u8],
+        public_inputs: &[Fp],
+        max_age: Duration,
+    ) -> Result<bool, ZkitError> {
+        let max_age = max_age.as_secs();
+        let age = match proof_info(proof)?.timestamp {
+            Some(timestamp) => unix_now().saturating_sub(timestamp),
+            None => u64::MAX,
+        };
+        if age > max_age {
+            return Err(ZkitError::Expired { age, max_age });
+        }
+        self.verify_proof_with_inputs(proof, public_inputs)
+    }
+
+    fn verify_cache_hits(&self) -> u64 {
+        self.verify_cache.as_ref().map_or(0, |cache| cache.lock().unwrap().hits)
+    }
+
+    // Single proving path shared by the example circuit and every statement circuit.
+    fn prove_with(
+        &self,
+        pk: &ProvingKey<Fp>,
+        circuit: &(impl Circuit<Fp> + Sync),
+        instances: &[&[Fp]],
+        binding: &TranscriptBinding,
+    ) -> Result<Vec<u8>, ZkitError> {
+        self.in_pool(|| match self.transcript {
+            TranscriptKind::Blake2b => {
+                let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+                self.prove_into(pk, circuit, instances, binding, &mut transcript)?;
+                Ok(transcript.finalize())
+            }
+            TranscriptKind::Keccak => {
+                let mut transcript = KeccakWrite::init(vec![]);
+                self.prove_into(pk, circuit, instances, binding, &mut transcript)?;
+                Ok(transcript.finalize())
+            }
+        })
+    }
+
+    // halo2 parallelises with rayon, on whichever pool the calling thread belongs to. Running the
+    // work inside the instance's pool keeps it there; without one it lands on the global pool.
+    fn in_pool<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
+    fn prove_into(
+        &self,
+        pk: &ProvingKey<Fp>,
+        circuit: &impl Circuit<Fp>,
+        instances: &[&[Fp]],
+        binding: &TranscriptBinding,
+        transcript: &mut impl TranscriptWrite<EqAffine, Challenge255<EqAffine>>,
+    ) -> Result<(), ZkitError> {
+        let vk_fingerprint = self.absorb_vk.then(|| fingerprint_vk(pk.get_vk()));
+        absorb_preamble(self.domain.as_deref(), vk_fingerprint.as_ref(), binding, transcript)?;
+        create_proof(
+            &self.params,
+            pk,
+            &[circuit],
+            &[instances],
+            self.proof_rng()?,
+            transcript,
+        )?;
+        Ok(())
+    }
+
+    fn verify_with(
+        &self,
+        vk: &VerifyingKey<Fp>,
+        instances: &[&[Fp]],
+        proof: &[u8],
+        binding: &TranscriptBinding,
+        mode: VerifyMode,
+    ) -> Result<bool, ZkitError> {
+        let domain = self.domain.as_deref();
+        let vk_fingerprint = self.absorb_vk.then(|| fingerprint_vk(vk));
+        self.in_pool(|| {
+            verify_transcript(&self.params, vk, self.transcript, domain, vk_fingerprint.as_ref(), instances, proof, binding, mode)
+        })
+    }
+
+    // Statement circuits get keys generated on demand from their shape rather than the instance keys.
+    fn prove_statement(&self, circuit: &(impl Circuit<Fp> + Sync), instances: &[&[Fp]]) -> Result<Vec<u8>, ZkitError> {
+        let vk = keygen_vk(&self.params, circuit)?;
+        let pk = keygen_pk(&self.params, vk, circuit)?;
+        self.prove_with(&pk, circuit, instances, &TranscriptBinding::default())
+    }
+
+    fn verify_statement(
+        &self,
+        circuit: &impl Circuit<Fp>,
+        instances: &[&[Fp]],
+        proof: &[u8],
+    ) -> Result<bool, ZkitError> {
+        let vk = keygen_vk(&self.params, circuit)?;
+        self.verify_with(&vk, instances, proof, &TranscriptBinding::default(), self.verify_mode)
+    }
+
+    fn prove_sum(&self, id: u64, claimed_sum: Fp) -> Result<Vec<u8>, ZkitError> {
+        let record = self.value_record(id)?;
+        if record.data.is_empty() {
+            return Err(ZkitError::EmptyRecord(id));
+        }
+        let circuit = SumCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        self.prove_statement(&circuit, &[&[record.digest()], &[claimed_sum]])
+    }
+
+    // The record's hash is part of the instance, so verify_count checks against its own copy.
+    fn prove_count(&self, id: u64, value: u8, claimed_count: u64) -> Result<Vec<u8>, ZkitError> {
+        let record = self.value_record(id)?;
+        if record.data.is_empty() {
+            return Err(ZkitError::EmptyRecord(id));
+        }
+        let circuit = CountCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        let public = [Fp::from(value as u64), Fp::from(claimed_count)];
+        self.prove_statement(&circuit, &[&[record.digest()], &public])
+    }
+
+    fn verify_count(&self, id: u64, value: u8, claimed_count: u64, proof: &[u8]) -> Result<bool, ZkitError> {
+        let record = self.record(id)?;
+        let circuit = CountCircuit {
+            values: vec![Value::unknown(); record.data.len()],
+        };
+        let public = [Fp::from(value as u64), Fp::from(claimed_count)];
+        self.verify_statement(&circuit, &[&[record.digest()], &public], proof)
+    }
+
+    // claimed_bits is the record's total Hamming weight over its bytes. The instance carries the
+    // record's hash, so verify_popcount checks against its own copy.
+    fn prove_popcount(&self, id: u64, claimed_bits: u64) -> Result<Vec<u8>, ZkitError> {
+        let record = self.value_record(id)?;
+        if record.data.is_empty() {
+            return Err(ZkitError::EmptyRecord(id));
+        }
+        if record.encoding != Encoding::Bytewise {
+            return Err(ZkitError::UnsupportedEncoding(record.encoding));
+        }
+        let circuit = PopcountCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        self.prove_statement(&circuit, &[&[record.digest()], &[Fp::from(claimed_bits)]])
+    }
+
+    fn verify_popcount(&self, id: u64, claimed_bits: u64, proof: &[u8]) -> Result<bool, ZkitError> {
+        let record = self.record(id)?;
+        let circuit = PopcountCircuit {
+            values: vec![Value::unknown(); record.data.len()],
+        };
+        self.verify_statement(&circuit, &[&[record.digest()], &[Fp::from(claimed_bits)]], proof)
+    }
+
+    // The instance is the record's hash, lo and hi, so the verifier needs the record's digest
+    // and learns nothing else about its values.
+    fn prove_bounded(&self, id: u64, lo: Fp, hi: Fp) -> Result<Vec<u8>, ZkitError> {
+        let record = self.value_record(id)?;
+        if let Some(index) = record.data.iter().position(|&x| !gap_in_range(lo, x) || !gap_in_range(x, hi)) {
+            return Err(ZkitError::OutOfBounds { index });
+        }
+        let circuit = BoundedCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        self.prove_statement(&circuit, &[&[record.digest(), lo, hi]])
+    }
+
+    fn verify_bounded(&self, id: u64, lo: Fp, hi: Fp, proof: &[u8]) -> Result<bool, ZkitError> {
+        let record = self.record(id)?;
+        let circuit = BoundedCircuit {
+            values: vec![Value::unknown(); record.data.len()],
+        };
+        self.verify_statement(&circuit, &[&[record.digest(), lo, hi]], proof)
+    }
+
+    fn prove_utf8(&self, id: u64) -> Result<Vec<u8>, ZkitError> {
+        let record = self.value_record(id)?;
+        if record.encoding != Encoding::Bytewise {
+            return Err(ZkitError::UnsupportedEncoding(record.encoding));
+        }
+        let bytes = record.data.iter().map(fp_to_byte).collect::<Result<Vec<u8>, _>>()?;
+        if let Err(e) = std::str::from_utf8(&bytes) {
+            return Err(ZkitError::InvalidUtf8 { position: e.valid_up_to() });
+        }
+        let circuit = Utf8Circuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        self.prove_statement(&circuit, &[&[record_hash(&record.data)]])
+    }
+
+    fn verify_utf8(&self, id: u64, proof: &[u8]) -> Result<bool, ZkitError> {
+        let record = self.record(id)?;
+        let circuit = Utf8Circuit {
+            values: vec![Value::unknown(); record.data.len()],
+        };
+        self.verify_statement(&circuit, &[&[record_hash(&record.data)]], proof)
+    }
+
+    fn prove_xor_checksum(&self, id: u64, claimed: u8) -> Result<Vec<u8>, ZkitError> {
+        let record = self.value_record(id)?;
+        if record.encoding != Encoding::Bytewise {
+            return Err(ZkitError::UnsupportedEncoding(record.encoding));
+        }
+        let circuit = XorCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        self.prove_statement(&circuit, &[&[record_hash(&record.data), Fp::from(claimed as u64)]])
+    }
+
+    fn verify_xor_checksum(&self, id: u64, claimed: u8, proof: &[u8]) -> Result<bool, ZkitError> {
+        let record = self.record(id)?;
+        let circuit = XorCircuit {
+            values: vec![Value::unknown(); record.data.len()],
+        };
+        self.verify_statement(&circuit, &[&[record_hash(&record.data), Fp::from(claimed as u64)]], proof)
+    }
+
+    // Instance for prove_exists_matching: the predicate value, then every record's record_hash in id
+    // order, so prover and verifier must hold the same store.
+    fn exists_instance(&self, predicate: &PredicateParams) -> Vec<Fp> {
+        let storage = self.storage.read().unwrap();
+        let mut ids: Vec<u64> = storage.keys().copied().collect();
+        ids.sort_unstable();
+        let mut instance = vec![predicate.value()];
+        instance.extend(ids.iter().map(|id| record_hash(&storage[id].data)));
+        instance
+    }
+
+    // Proves some stored record satisfies `predicate` without saying which. The lowest matching id
+    // is used. The proof is prefixed with that record's element count (u64 LE), which is therefore
+    // disclosed, narrowing the candidates to records of that length.
+    fn prove_exists_matching(&self, predicate: PredicateParams) -> Result<Vec<u8>, ZkitError> {
+        let data = {
+            let storage = self.storage.read().unwrap();
+            let mut ids: Vec<u64> = storage.keys().copied().collect();
+            ids.sort_unstable();
+            ids.into_iter()
+                .map(|id| &storage[&id].data)
+                .find(|data| predicate.holds(data))
+                .cloned()
+                .ok_or(ZkitError::NoMatchingRecord)?
+        };
+        let instance = self.exists_instance(&predicate);
+        let circuit = ExistsCircuit {
+            values: data.iter().map(|&x| Value::known(x)).collect(),
+            set_size: instance.len() - 1,
+        };
+        let mut out = (data.len() as u64).to_le_bytes().to_vec();
+        out.extend(self.prove_statement(&circuit, &[&instance])?);
+        Ok(out)
+    }
+
+    fn verify_exists_matching(&self, predicate: &PredicateParams, proof: &[u8]) -> Result<bool, ZkitError> {
+        let (len, proof) = self.split_len_prefix(proof)?;
+        let instance = self.exists_instance(predicate);
+        if len == 0 || instance.len() == 1 {
+            return Ok(false);
+        }
+        let circuit = ExistsCircuit {
+            values: vec![Value::unknown(); len],
+            set_size: instance.len() - 1,
+        };
+        self.verify_statement(&circuit, &[&instance], proof)
+    }
+
+    // Proves `value` occurs in no stored record. The instance is the same as prove_exists_matching's
+    // for Contains(value); the verifier takes the record lengths from its own store.
+    fn prove_non_membership(&self, value: Fp) -> Result<Vec<u8>, ZkitError> {
+        let records: Vec<(u64, Vec<Fp>)> = {
+            let storage = self.storage.read().unwrap();
+            let mut ids: Vec<u64> = storage.keys().copied().collect();
+            ids.sort_unstable();
+            ids.into_iter().map(|id| (id, storage[&id].data.clone())).collect()
+        };
+        if let Some((id, _)) = records.iter().find(|(_, data)| data.contains(&value)) {
+            return Err(ZkitError::ValuePresent(*id));
+        }
+        // Nothing to take a product over; the circuit needs at least one element.
+        if records.iter().all(|(_, data)| data.is_empty()) {
+            return Err(ZkitError::EmptyBatch);
+        }
+        let circuit = NonMembershipCircuit {
+            records: records.iter().map(|(_, data)| data.iter().map(|&x| Value::known(x)).collect()).collect(),
+        };
+        self.prove_statement(&circuit, &[&self.exists_instance(&PredicateParams::Contains(value))])
+    }
+
+    fn verify_non_membership(&self, value: Fp, proof: &[u8]) -> Result<bool, ZkitError> {
+        let records: Vec<Vec<Value<Fp>>> = {
+            let storage = self.storage.read().unwrap();
+            let mut ids: Vec<u64> = storage.keys().copied().collect();
+            ids.sort_unstable();
+            ids.into_iter().map(|id| vec![Value::unknown(); storage[&id].data.len()]).collect()
+        };
+        let instance = self.exists_instance(&PredicateParams::Contains(value));
+        self.verify_statement(&NonMembershipCircuit { records }, &[&instance], proof)
+    }
+
+    // Discloses the first prefix.len() bytes of a bytewise record through prove_disclosed; the rest
+    // stays witnessed. Same proof framing as prove_disclosed. Only zero padding leaves the data at
+    // element 0, so see value_record.
+    fn prove_prefix(&self, id: u64, prefix: &[u8]) -> Result<Vec<u8>, ZkitError> {
+        let record = self.value_record(id)?;
+        if record.encoding != Encoding::Bytewise {
+            return Err(ZkitError::UnsupportedEncoding(record.encoding));
+        }
+        if prefix.len() > record.orig_len {
+            return Err(ZkitError::IndexOutOfRange {
+                index: prefix.len() - 1,
+                len: record.orig_len,
+            });
+        }
+        if let Some(index) = prefix.iter().zip(&record.data).position(|(&b, x)| Fp::from(b as u64) != *x) {
+            return Err(ZkitError::PrefixMismatch { index });
+        }
+        let disclosed: Vec<usize> = (0..prefix.len()).collect();
+        Ok(self.prove_disclosed(id, &disclosed)?.0)
+    }
+
+    fn verify_prefix(&self, commitment: Fp, prefix: &[u8], proof: &[u8]) -> Result<bool, ZkitError> {
+        let disclosed: Vec<usize> = (0..prefix.len()).collect();
+        let values: Vec<Fp> = prefix.iter().map(|&b| Fp::from(b as u64)).collect();
+        self.verify_disclosed(commitment, &disclosed, &values, proof)
+    }
+
+    // Checked here first, so a record that doesn't fit the schema reports the first bad field
+    // (or the trailing partial one) instead of producing a proof that can't verify.
+    fn prove_schema(&self, id: u64, width: usize) -> Result<Vec<u8>, ZkitError> {
+        if !(2..=MAX_SCHEMA_WIDTH).contains(&width) {
+            return Err(ZkitError::BadSchemaWidth(width));
+        }
+        let record = self.value_record(id)?;
+        if record.encoding != Encoding::Bytewise {
+            return Err(ZkitError::UnsupportedEncoding(record.encoding));
+        }
+        let bytes: Vec<u8> = record.data.iter().map(fp_to_byte_lossy).collect();
+        if bytes.len() % width != 0 {
+            return Err(ZkitError::SchemaViolation { field: bytes.len() / width });
+        }
+        for (field, chunk) in bytes.chunks(width).enumerate() {
+            let (checksum, data) = chunk.split_last().unwrap();
+            if data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != *checksum {
+                return Err(ZkitError::SchemaViolation { field });
+            }
+        }
+        let circuit = SchemaCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+            width,
+        };
+        self.prove_statement(&circuit, &[&[record_hash(&record.data)]])
+    }
+
+    fn verify_schema(&self, id: u64, width: usize, proof: &[u8]) -> Result<bool, ZkitError> {
+        if !(2..=MAX_SCHEMA_WIDTH).contains(&width) {
+            return Err(ZkitError::BadSchemaWidth(width));
+        }
+        let record = self.record(id)?;
+        if record.data.len() % width != 0 {
+            return Ok(false);
+        }
+        let circuit = SchemaCircuit {
+            values: vec![Value::unknown(); record.data.len()],
+            width,
+        };
+        self.verify_statement(&circuit, &[&[record_hash(&record.data)]], proof)
+    }
+
+    fn permutation_instance(&self, id_a: u64, id_b: u64) -> Result<(Vec<Fp>, usize), ZkitError> {
+        let a = self.record(id_a)?;
+        let b = self.record(id_b)?;
+        if a.data.len() != b.data.len() {
+            return Err(ZkitError::LengthMismatch);
+        }
+        if a.data.is_empty() {
+            return Err(ZkitError::EmptyRecord(id_a));
+        }
+        let (hash_a, hash_b) = (record_hash(&a.data), record_hash(&b.data));
+        let instance = vec![Fp::from(id_a), Fp::from(id_b), hash_a, hash_b, poseidon2(hash_a, hash_b)];
+        Ok((instance, a.data.len()))
+    }
+
+    fn prove_permutation(&self, id_a: u64, id_b: u64) -> Result<Vec<u8>, ZkitError> {
+        let (instance, _) = self.permutation_instance(id_a, id_b)?;
+        let circuit = PermutationCircuit {
+            a: self.record(id_a)?.data.iter().map(|&x| Value::known(x)).collect(),
+            b: self.record(id_b)?.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        self.prove_statement(&circuit, &[&instance])
+    }
+
+    fn verify_permutation(&self, id_a: u64, id_b: u64, proof: &[u8]) -> Result<bool, ZkitError> {
+        let (instance, len) = self.permutation_instance(id_a, id_b)?;
+        let circuit = PermutationCircuit {
+            a: vec![Value::unknown(); len],
+            b: vec![Value::unknown(); len],
+        };
+        self.verify_statement(&circuit, &[&instance], proof)
+    }
+
+    // What prove_before commits to for a stored record; a ledger can publish these so a verifier
+    // can tell which entry a before proof is about.
+    fn entry_commitment(&self, id: u64) -> Result<Fp, ZkitError> {
+        Ok(entry_commitment(id, &self.record(id)?.data))
+    }
+
+    // The field sum of every record's entry_commitment, so it doesn't depend on insertion order
+    // or the map's iteration order: two instances holding the same ids with the same elements
+    // agree, without shipping a snapshot across. It covers the stored elements, so the same bytes
+    // under different encodings hash differently. The empty store hashes to zero.
+    fn store_hash(&self) -> Fp {
+        self.storage
+            .read()
+            .unwrap()
+            .iter()
+            .fold(Fp::zero(), |acc, (&id, record)| acc + poseidon2(Fp::from(id), record.digest()))
+    }
+
+    // Proof layout: element count (u64 LE) | entry commitment (32-byte repr) | proof. The verifier
+    // learns the threshold, the commitment and the element count, not the id or the data.
+    fn prove_before(&self, id: u64, threshold: u64) -> Result<Vec<u8>, ZkitError> {
+        if id >= threshold {
+            return Err(ZkitError::NotBefore { id, threshold });
+        }
+        let record = self.record(id)?;
+        let entry = entry_commitment(id, &record.data);
+        let circuit = BeforeCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+            id: Value::known(Fp::from(id)),
+        };
+        let mut out = (record.data.len() as u64).to_le_bytes().to_vec();
+        out.extend_from_slice(entry.to_repr().as_ref());
+        out.extend(self.prove_statement(&circuit, &[&[Fp::from(threshold), entry]])?);
+        Ok(out)
+    }
+
+    // The entry commitment a prove_before proof is about, to compare against entry_commitment.
+    fn before_commitment(proof: &[u8]) -> Result<Fp, ZkitError> {
+        let mut input = proof.get(8..).ok_or(ZkitError::MalformedProof)?;
+        read_fp(&mut input).map_err(|_| ZkitError::MalformedProof)
+    }
+
+    fn verify_before(&self, threshold: u64, proof: &[u8]) -> Result<bool, ZkitError> {
+        let entry = Self::before_commitment(proof)?;
+        let (len, _) = self.split_len_prefix(proof)?;
+        let circuit = BeforeCircuit {
+            values: vec![Value::unknown(); len],
+            id: Value::unknown(),
+        };
+        self.verify_statement(&circuit, &[&[Fp::from(threshold), entry]], &proof[40..])
+    }
+
+    fn concat_instance(&self, id_a: u64, id_b: u64, id_ab: u64) -> Result<(Vec<Fp>, [usize; 3]), ZkitError> {
+        let a = self.record(id_a)?;
+        let b = self.record(id_b)?;
+        let ab = self.record(id_ab)?;
+        let instance = vec![
+            Fp::from(id_a),
+            Fp::from(id_b),
+            Fp::from(id_ab),
+            record_hash(&a.data),
+            record_hash(&b.data),
+            record_hash(&ab.data),
+        ];
+        Ok((instance, [a.data.len(), b.data.len(), ab.data.len()]))
+    }
+
+    // Compares stored elements, so a Packed record only concatenates where the join falls on a
+    // chunk boundary. A mismatch is reported here rather than producing a proof that can't verify.
+    fn prove_concat(&self, id_a: u64, id_b: u64, id_ab: u64) -> Result<Vec<u8>, ZkitError> {
+        let (instance, _) = self.concat_instance(id_a, id_b, id_ab)?;
+        let a = self.record(id_a)?.data;
+        let b = self.record(id_b)?.data;
+        let ab = self.record(id_ab)?.data;
+        if let Some(index) = a.iter().chain(&b).zip(&ab).position(|(x, y)| x != y) {
+            return Err(ZkitError::NotConcatenation { index });
+        }
+        if a.len() + b.len() != ab.len() {
+            return Err(ZkitError::NotConcatenation { index: ab.len().min(a.len() + b.len()) });
+        }
+        let circuit = ConcatCircuit {
+            a: a.iter().map(|&x| Value::known(x)).collect(),
+            b: b.iter().map(|&x| Value::known(x)).collect(),
+            ab: ab.iter().map(|&x| Value::known(x)).collect(),
+        };
+        self.prove_statement(&circuit, &[&instance])
+    }
+
+    fn verify_concat(&self, id_a: u64, id_b: u64, id_ab: u64, proof: &[u8]) -> Result<bool, ZkitError> {
+        let (instance, [len_a, len_b, len_ab]) = self.concat_instance(id_a, id_b, id_ab)?;
+        if len_a + len_b != len_ab {
+            return Ok(false);
+        }
+        let circuit = ConcatCircuit {
+            a: vec![Value::unknown(); len_a],
+            b: vec![Value::unknown(); len_b],
+            ab: vec![Value::unknown(); len_ab],
+        };
+        self.verify_statement(&circuit, &[&instance], proof)
+    }
+
+    fn linked_instance(&self, id_a: u64, id_b: u64, shared: Fp) -> Result<(Vec<Fp>, usize, usize), ZkitError> {
+        let a = self.record(id_a)?;
+        let b = self.record(id_b)?;
+        for (id, record) in [(id_a, &a), (id_b, &b)] {
+            if record.data.is_empty() {
+                return Err(ZkitError::EmptyRecord(id));
+            }
+        }
+        let instance = vec![record_hash(&a.data), record_hash(&b.data), shared];
+        Ok((instance, a.data.len(), b.data.len()))
+    }
+
+    fn prove_linked(&self, id_a: u64, id_b: u64, shared: Fp) -> Result<Vec<u8>, ZkitError> {
+        let (instance, _, _) = self.linked_instance(id_a, id_b, shared)?;
+        let circuit = LinkedCircuit {
+            a: self.record(id_a)?.data.iter().map(|&x| Value::known(x)).collect(),
+            b: self.record(id_b)?.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        self.prove_statement(&circuit, &[&instance])
+    }
+
+    fn verify_linked(&self, id_a: u64, id_b: u64, shared: Fp, proof: &[u8]) -> Result<bool, ZkitError> {
+        let (instance, len_a, len_b) = self.linked_instance(id_a, id_b, shared)?;
+        let circuit = LinkedCircuit {
+            a: vec![Value::unknown(); len_a],
+            b: vec![Value::unknown(); len_b],
+        };
+        self.verify_statement(&circuit, &[&instance], proof)
+    }
+
+    fn prove_combined(&self, id: u64, statements: &[Statement]) -> Result<Vec<u8>, ZkitError> {
+        let record = self.value_record(id)?;
+        if record.data.is_empty() {
+            return Err(ZkitError::EmptyRecord(id));
+        }
+        let circuit = CombinedCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+            statements: statements.to_vec(),
+        };
+        self.prove_statement(&circuit, &[&[record.digest()], &combined_instance(statements)])
+    }
+
+    fn verify_combined(&self, id: u64, statements: &[Statement], proof: &[u8]) -> Result<bool, ZkitError> {
+        let record = self.record(id)?;
+        let circuit = CombinedCircuit {
+            values: vec![Value::unknown(); record.data.len()],
+            statements: statements.to_vec(),
+        };
+        self.verify_statement(&circuit, &[&[record.digest()], &combined_instance(statements)], proof)
+    }
+
+    // The record's hash is part of the instance, so the proof has to be about this record.
+    fn verify_sum(&self, id: u64, claimed_sum: Fp, proof: &[u8]) -> Result<bool, ZkitError> {
+        let record = self.record(id)?;
+        let circuit = SumCircuit {
+            values: vec![Value::unknown(); record.data.len()],
+        };
+        self.verify_statement(&circuit, &[&[record.digest()], &[claimed_sum]], proof)
+    }
+
+    // Dry configure only; the witness row count isn't known here, so estimated_rows is the floor
+    // every proof pays (blinding plus the reserved tail) before any assignments are added.
+    fn circuit_stats<C: Circuit<Fp>>(&self, _circuit: &C) -> CircuitStats {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        C::configure(&mut meta);
+        CircuitStats {
+            advice_columns: meta.num_advice_columns(),
+            fixed_columns: meta.num_fixed_columns(),
+            instance_columns: meta.num_instance_columns(),
+            selectors: meta.num_selectors(),
+            gates: meta.gates().len(),
+            estimated_rows: meta.minimum_rows(),
+        }
+    }
+
+    // Column counts, gates and the regions synthesize lays out, as an ASCII table.
+    fn dump_layout<C: Circuit<Fp>>(&self, circuit: &C, out: &mut dyn Write) -> Result<(), ZkitError> {
+        let mut meta = ConstraintSystem::<Fp>::default();
+        let config = C::configure(&mut meta);
+        let mut recorder = LayoutRecorder::default();
+        C::FloorPlanner::synthesize(&mut recorder, circuit, config, meta.constants().clone())?;
+        recorder.exit_region();
+
+        writeln!(
+            out,
+            "advice columns: {}  fixed columns: {}  instance columns: {}  selectors: {}  lookups: {}",
+            meta.num_advice_columns(),
+            meta.num_fixed_columns(),
+            meta.num_instance_columns(),
+            meta.num_selectors(),
+            meta.lookups().len(),
+        )?;
+        writeln!(out, "gates:")?;
+        for gate in meta.gates() {
+            writeln!(out, "  {} ({} constraints)", gate.name(), gate.polynomials().len())?;
+        }
+        writeln!(out, "{:<28} {:>12} {:>7} {:>9}  assignments", "region", "rows", "cells", "selectors")?;
+        for region in &recorder.regions {
+            let rows = region.rows.map_or("-".to_string(), |(lo, hi)| format!("{}..={}", lo, hi));
+            writeln!(
+                out,
+                "{:<28} {:>12} {:>7} {:>9}  {}",
+                region.name,
+                rows,
+                region.cells,
+                region.selectors,
+                region.annotations.join(", "),
+            )?;
+        }
+        writeln!(out, "copy constraints: {}", recorder.copies)?;
+        Ok(())
+    }
+
+    // Runs n ingest/prove/verify cycles over random records, each forgotten again afterwards. A cycle
+    // fails if any stage errors or the proof doesn't verify.
+    fn loadtest(&self, n: usize) -> LoadtestReport {
+        let start = Instant::now();
+        let mut latencies = Vec::with_capacity(n);
+        let mut verified = 0;
+        for _ in 0..n {
+            let mut data = vec![0u8; LOADTEST_RECORD_BYTES];
+            OsRng.fill_bytes(&mut data);
+            let cycle = Instant::now();
+            let ok = match self.batch_and_inscribe(data) {
+                Ok(id) => {
+                    let result = self.prove(id.0, &[]).and_then(|proof| self.verify_proof(&proof));
+                    self.forget(id);
+                    matches!(result, Ok(true))
+                }
+                Err(_) => false,
+            };
+            latencies.push(cycle.elapsed());
+            verified += ok as usize;
+        }
+        latencies.sort_unstable();
+        let percentile = |p: usize| {
+            let idx = (latencies.len() * p / 100).min(latencies.len().saturating_sub(1));
+            latencies.get(idx).copied().unwrap_or_default()
+        };
+        LoadtestReport {
+            cycles: n,
+            verified,
+            failed: n - verified,
+            elapsed: start.elapsed(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+        }
+    }
+
+    // Proves a stored record with the instance keys; proofs are cached per (id, public inputs).
+    fn prove(&self, id: u64, public_inputs: &[Fp]) -> Result<Vec<u8>, ZkitError> {
+        self.log_access(AccessKind::Prove, Some(id));
+        let key = (id, inputs_hash(public_inputs));
+        if let Some(cached) = self.proof_cache.read().unwrap().get(&key) {
+            return Ok(expand_proof(&cached.proof)?.into_owned());
+        }
+        if let Some(proof) = self.load_disk_proof(id, public_inputs)? {
+            return Ok(proof);
+        }
+        self.prove_and_cache(id, public_inputs)
+    }
+
+    // {id}-{public inputs hash}-{record checksum}-{vk fingerprint}.proof, so an overwritten record
+    // never matches a file written for its old contents, and neither do proofs made under keys
+    // since replaced. Files for old keys are left behind for the operator to clear. Without keys
+    // there is nothing to name a file after.
+    fn disk_proof_path(&self, id: u64, public_inputs: &[Fp], record_checksum: &[u8; 32]) -> Option<PathBuf> {
+        let dir = self.proof_dir.as_ref()?;
+        let fingerprint = self.vk_fingerprint()?;
+        let name = format!(
+            "{}-{}-{}-{}.proof",
+            id,
+            hex::encode(inputs_hash(public_inputs)),
+            hex::encode(record_checksum),
+            hex::encode(fingerprint)
+        );
+        Some(dir.join(name))
+    }
+
+    // A hit is copied into the in-memory cache. A missing or unreadable file is just a miss.
+    fn load_disk_proof(&self, id: u64, public_inputs: &[Fp]) -> Result<Option<Vec<u8>>, ZkitError> {
+        if self.proof_dir.is_none() {
+            return Ok(None);
+        }
+        let record_checksum = self.record(id)?.checksum;
+        let path = match self.disk_proof_path(id, public_inputs, &record_checksum) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let stored = match fs::read(&path) {
+            Ok(stored) => stored,
+            Err(_) => return Ok(None),
+        };
+        let proof = match expand_proof(&stored) {
+            Ok(proof) => proof.into_owned(),
+            Err(_) => return Ok(None),
+        };
+        self.proof_cache.write().unwrap().insert(
+            (id, inputs_hash(public_inputs)),
+            CachedProof {
+                proof: stored,
+                public_inputs: public_inputs.to_vec(),
+                record_checksum,
+            },
+        );
+        Ok(Some(proof))
+    }
+
+    // Written to a temporary name and renamed into place so a reader never sees a partial proof.
+    fn store_disk_proof(&self, id: u64, entry: &CachedProof) -> io::Result<()> {
+        let path = match self.disk_proof_path(id, &entry.public_inputs, &entry.record_checksum) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        // Writers of the same proof, in this process or another, each get their own temp file, so
+        // the rename only ever publishes a complete one.
+        let seq = DISK_PROOF_TMP.fetch_add(1, Ordering::Relaxed);
+        let tmp = path.with_extension(format!("proof.{}-{}.tmp", std::process::id(), seq));
+        let result = fs::write(&tmp, &entry.proof).and_then(|()| fs::rename(&tmp, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    // Instance columns the verifying key was built for; verify_proof wants public inputs in
+    // multiples of this so every column gets the same number of rows.
+    fn vk_instance_arity(&self) -> Option<usize> {
+        self.instance_keys().ok().map(|keys| keys.vk.cs().num_instance_columns())
+    }
+
+    fn begin_collaborative_proof(&self, id: u64) -> Result<ProofSession<'_>, ZkitError> {
+        let len = self.record(id)?.data.len();
+        Ok(ProofSession { zkit: self, id, sum: vec![Fp::zero(); len] })
+    }
+
+    fn prove_and_cache(&self, id: u64, public_inputs: &[Fp]) -> Result<Vec<u8>, ZkitError> {
+        let key = (id, inputs_hash(public_inputs));
+        let record = self.record(id)?;
+        let record_checksum = record.checksum;
+        let circuit: ExampleCircuit<Fp> = ExampleCircuit {
+            data: record.data,
+            lookup: None,
+            _marker: PhantomData,
+        };
+        let proof = self.create_proof_with_inputs(&circuit, public_inputs)?;
+        let entry = CachedProof {
+            proof: if self.compress_proofs { compress_proof(&proof) } else { proof.clone() },
+            public_inputs: public_inputs.to_vec(),
+            record_checksum,
+        };
+        // The disk copy is an optimisation; failing to write it doesn't fail the proof.
+        let _ = self.store_disk_proof(id, &entry);
+        self.proof_cache.write().unwrap().insert(key, entry);
+        Ok(proof)
+    }
+
+    // Proves every cached (record, public inputs) pair again with the current keys, spread over the
+    // available cores. An id is reported once, with the first failure among its cached proofs.
+    fn reprove_all(&self) -> Vec<(u64, Result<(), ZkitError>)> {
+        let entries: Vec<(u64, Vec<Fp>)> = self
+            .proof_cache
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(&(id, _), entry)| (id, entry.public_inputs.clone()))
+            .collect();
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let chunk = (entries.len() + workers - 1) / workers;
+
+        let results: Vec<(u64, Result<(), ZkitError>)> = thread::scope(|scope| {
+            let handles: Vec<_> = entries
+                .chunks(chunk)
+                .map(|batch| {
+                    scope.spawn(move || {
+                        batch
+                            .iter()
+                            .map(|(id, inputs)| (*id, self.prove_and_cache(*id, inputs).map(|_| ())))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .zip(entries.chunks(chunk))
+                .flat_map(|(handle, batch)| {
+                    handle.join().unwrap_or_else(|_| {
+                        batch.iter().map(|(id, _)| (*id, Err(ZkitError::WorkerFailed))).collect()
+                    })
+                })
+                .collect()
+        });
+
+        let mut outcomes: HashMap<u64, Result<(), ZkitError>> = HashMap::new();
+        for (id, result) in results {
+            let slot = outcomes.entry(id).or_insert(Ok(()));
+            if slot.is_ok() {
+                *slot = result;
+            }
+        }
+        let mut outcomes: Vec<_> = outcomes.into_iter().collect();
+        outcomes.sort_unstable_by_key(|(id, _)| *id);
+        outcomes
+    }
+
+    // Re-checks every cached proof for a record: each must have been made against the record as it
+    // is now and must still verify. Stale entries are dropped before the error is returned.
+    fn audit(&self, id: u64) -> Result<(), ZkitError> {
+        let current = self.record(id)?.checksum;
+        let cached: Vec<CachedProof> = self
+            .proof_cache
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|((cached, _), _)| *cached == id)
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        for entry in cached {
+            if entry.record_checksum != current
+                || !self.verify_proof_with_inputs(&entry.proof, &entry.public_inputs)?
+            {
+                self.invalidate_proof(id);
+                return Err(ZkitError::StaleProof(id));
+            }
+        }
+        Ok(())
+    }
+
+    // Verifies every cached proof against its record, sorted by id. An id is reported once: false
+    // if any of its proofs fails or was made for the record's old contents, an error if one can't
+    // be checked at all. Nothing is invalidated; audit does that for a single record.
+    fn verify_all(&self) -> Vec<(u64, Result<bool, ZkitError>)> {
+        let mut cached: Vec<(u64, CachedProof)> = self
+            .proof_cache
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(&(id, _), entry)| (id, entry.clone()))
+            .collect();
+        cached.sort_by_key(|(id, _)| *id);
+
+        let mut outcomes: Vec<(u64, Result<bool, ZkitError>)> = Vec::new();
+        for (id, entry) in cached {
+            if let Some((last, outcome)) = outcomes.last() {
+                if *last == id && !matches!(outcome, Ok(true)) {
+                    continue;
+                }
+            }
+            let outcome = self.record(id).and_then(|record| {
+                if record.checksum != entry.record_checksum {
+                    return Ok(false);
+                }
+                self.verify_proof_with_inputs(&entry.proof, &entry.public_inputs)
+            });
+            match outcomes.last_mut() {
+                Some((last, slot)) if *last == id => *slot = outcome,
+                _ => outcomes.push((id, outcome)),
+            }
+        }
+        outcomes
+    }
+
+    // Rows available to a single proof at the current k.
+    fn circuit_capacity(&self) -> usize {
+        (1usize << self.params.k()).saturating_sub(Self::ROW_OVERHEAD)
+    }
+
+    // Splits the u64 LE element count off a length-prefixed statement proof. The count comes from
+    // the proof, so it is checked against what a circuit at this k could hold before anything is
+    // sized from it.
+    fn split_len_prefix<'a>(&self, proof: &'a [u8]) -> Result<(usize, &'a [u8]), ZkitError> {
+        if proof.len() < 8 {
+            return Err(ZkitError::MalformedProof);
+        }
+        let (len, rest) = proof.split_at(8);
+        let len = u64::from_le_bytes(len.try_into().unwrap());
+        if len > self.circuit_capacity() as u64 {
+            return Err(ZkitError::MalformedProof);
+        }
+        Ok((len as usize, rest))
+    }
+
+    // Rows one element takes in record_hash_gadget: a Pow5 permutation (8 full rounds one row each,
+    // 56 partial rounds two to a row) plus the rows loading its state and input.
+    const HASH_ROWS_PER_ELEMENT: usize = 40;
+
+    fn chunk_len(&self) -> usize {
+        (self.circuit_capacity() / Self::HASH_ROWS_PER_ELEMENT).max(1)
+    }
+
+    // One HashCircuit proof per chunk, committing to record_hash of its slice. Each instance is
+    // (chunk hash, parent id, chunk index, chunk count), so chunks can't be reordered, dropped, or
+    // spliced in from another record, and verify_chunked recomputes every hash from its own copy.
+    fn prove_chunked(&self, id: u64) -> Result<Vec<Vec<u8>>, ZkitError> {
+        let record = self.record(id)?;
+        let chunks: Vec<&[Fp]> = record.data.chunks(self.chunk_len()).collect();
+        let count = Fp::from(chunks.len() as u64);
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(idx, chunk)| {
+                let circuit = HashCircuit {
+                    values: chunk.iter().map(|&x| Value::known(x)).collect(),
+                };
+                self.prove_statement(&circuit, &[&[record_hash(chunk), Fp::from(id), Fp::from(idx as u64), count]])
+            })
+            .collect()
+    }
+
+    fn verify_chunked(&self, id: u64, proofs: &[Vec<u8>]) -> Result<bool, ZkitError> {
+        let record = self.record(id)?;
+        let chunks: Vec<&[Fp]> = record.data.chunks(self.chunk_len()).collect();
+        if proofs.len() != chunks.len() {
+            return Ok(false);
+        }
+        let count = Fp::from(chunks.len() as u64);
+        for (idx, (chunk, proof)) in chunks.iter().zip(proofs).enumerate() {
+            let circuit = HashCircuit {
+                values: vec![Value::unknown(); chunk.len()],
+            };
+            let instance = [record_hash(chunk), Fp::from(id), Fp::from(idx as u64), count];
+            if !self.verify_statement(&circuit, &[&instance], proof)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Rough model: a halo2 proof costs about PROVE_COMMITS full-size commitments at the current k, and
+    // a record larger than circuit_capacity needs one proof per chunk. The commitment cost is measured
+    // once per instance on first use. Memory counts the extended-domain polynomials held while proving.
+    const PROVE_COMMITS: u32 = 24;
+    const PROVE_POLYS_RESIDENT: usize = 16;
+
+    fn estimate_proof_cost(&self, id: u64) -> Result<ProofCostEstimate, ZkitError> {
+        let rows = self.record(id)?.data.len();
+        let capacity = self.circuit_capacity().max(1);
+        let proofs = ((rows + capacity - 1) / capacity).max(1);
+        let commit_cost = *self.commit_cost.get_or_init(|| {
+            let domain = EvaluationDomain::<Fp>::new(1, self.params.k());
+            let mut poly = domain.empty_coeff();
+            for x in poly.iter_mut() {
+                *x = Fp::one();
+            }
+            let start = Instant::now();
+            let _ = self.params.commit(&poly, Blind::default());
+            start.elapsed()
+        });
+        let n = 1usize << self.params.k();
+        Ok(ProofCostEstimate {
+            proofs,
+            duration: commit_cost * Self::PROVE_COMMITS * proofs as u32,
+            memory_bytes: n * 32 * Self::PROVE_POLYS_RESIDENT,
+        })
+    }
+
+    fn cache_stats(&self) -> CacheStats {
+        self.proof_cache.read().unwrap().stats()
+    }
+
+    // Keeps at most n cached proofs, dropping the oldest by creation time, now and on every later
+    // insert. Unlike proof_cache_limit, reading a proof doesn't keep it. The disk cache is untouched.
+    fn set_proof_retention(&self, n: usize) {
+        self.proof_cache.write().unwrap().set_retention(Some(n));
+    }
+
+    // Drops every in-memory cached proof; the disk cache, if any, is left alone.
+    fn clear_proof_cache(&self) {
+        self.proof_cache.write().unwrap().clear();
+    }
+
+    fn invalidate_proof(&self, id: u64) {
+        self.proof_cache.write().unwrap().retain(|&(cached, _), _| cached != id);
+        if let Some(entries) = self.proof_dir.as_ref().and_then(|dir| fs::read_dir(dir).ok()) {
+            let prefix = format!("{}-", id);
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+
+    // The hash circuit's shape depends on the record length, so the proof is prefixed with it
+    // (u64 LE); that lets a verifier without the record rebuild the circuit shape.
+    fn prove_matches_hash(&self, id: u64, expected: Fp) -> Result<Vec<u8>, ZkitError> {
+        let record = self.record(id)?;
+        let circuit = HashCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        let mut out = (record.data.len() as u64).to_le_bytes().to_vec();
+        out.extend(self.prove_statement(&circuit, &[&[expected]])?);
+        Ok(out)
+    }
+
+    fn verify_matches_hash(&self, expected: Fp, proof: &[u8]) -> Result<bool, ZkitError> {
+        let (len, proof) = self.split_len_prefix(proof)?;
+        let circuit = HashCircuit {
+            values: vec![Value::unknown(); len],
+        };
+        self.verify_statement(&circuit, &[&[expected]], proof)
+    }
+
+    // ZKIT has no separate commit-reveal store, so a record's commitment is its record_hash: what
+    // prove_matches_hash and the other statement circuits already expose for it.
+    fn record_commitment(&self, id: u64) -> Result<Fp, ZkitError> {
+        Ok(self.record(id)?.digest())
+    }
+
+    // Proves `data` opens record id's commitment; the verifier sees only the commitment and the
+    // element count. data is encoded with the record's own codec and padding, and a mismatch is
+    // reported here rather than producing a proof that can't verify.
+    fn prove_preimage(&self, id: u64, data: &[u8]) -> Result<Vec<u8>, ZkitError> {
+        let record = self.record(id)?;
+        // A based Delta record's elements depend on another record's bytes as well.
+        if delta_base(&record).is_some() {
+            return Err(ZkitError::UnsupportedEncoding(record.encoding));
+        }
+        let candidate = ingest_and_compress(data.to_vec(), self.codec(record.encoding)?.as_ref(), record.padding);
+        if candidate.checksum != record.checksum {
+            return Err(ZkitError::ChecksumMismatch(id));
+        }
+        let circuit = HashCircuit {
+            values: candidate.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        let mut out = (candidate.data.len() as u64).to_le_bytes().to_vec();
+        out.extend(self.prove_statement(&circuit, &[&[record_hash(&candidate.data)]])?);
+        Ok(out)
+    }
+
+    fn verify_preimage(&self, commitment: Fp, proof: &[u8]) -> Result<bool, ZkitError> {
+        self.verify_matches_hash(commitment, proof)
+    }
+
+    // Ingests every item, then proves all of them in one BatchCircuit proof whose instance is each
+    // record's record_hash in id order. The proof is prefixed with the item count and each record's
+    // element count (u64 LE). If any ingest fails, the items already stored are forgotten again.
+    fn ingest_batch_and_prove(&self, items: Vec<Vec<u8>>) -> Result<(Vec<u64>, Vec<u8>), ZkitError> {
+        if items.is_empty() {
+            return Err(ZkitError::EmptyBatch);
+        }
+        let mut ids = Vec::with_capacity(items.len());
+        for item in items {
+            match self.batch_and_inscribe(item) {
+                Ok(id) => ids.push(id.0),
+                Err(e) => {
+                    for &id in &ids {
+                        self.forget(RecordId(id));
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        let records = ids.iter().map(|&id| self.record(id).map(|r| r.data)).collect::<Result<Vec<_>, _>>()?;
+        let hashes: Vec<Fp> = records.iter().map(|data| record_hash(data)).collect();
+        let mut out = (records.len() as u64).to_le_bytes().to_vec();
+        for data in &records {
+            out.extend((data.len() as u64).to_le_bytes());
+        }
+        let circuit = BatchCircuit {
+            records: records.iter().map(|data| data.iter().map(|&x| Value::known(x)).collect()).collect(),
+        };
+        out.extend(self.prove_statement(&circuit, &[&hashes])?);
+        Ok((ids, out))
+    }
+
+    // Checks a batch proof against the records the store holds under `ids`, in the same order.
+    fn verify_ingested_batch(&self, ids: &[u64], proof: &[u8]) -> Result<bool, ZkitError> {
+        let mut input = proof;
+        let count = read_u64(&mut input).map_err(|_| ZkitError::MalformedProof)?;
+        if count != ids.len() as u64 {
+            return Err(ZkitError::LengthMismatch);
+        }
+        // The records share one circuit, so their lengths are bounded together.
+        let mut remaining = self.circuit_capacity();
+        let mut records = Vec::with_capacity(ids.len());
+        for _ in 0..count {
+            let (len, rest) = self.split_len_prefix(input)?;
+            remaining = remaining.checked_sub(len).ok_or(ZkitError::MalformedProof)?;
+            records.push(vec![Value::unknown(); len]);
+            input = rest;
+        }
+        let hashes = ids.iter().map(|&id| self.record_commitment(id)).collect::<Result<Vec<_>, _>>()?;
+        self.verify_statement(&BatchCircuit { records }, &[&hashes], input)
+    }
+
+    // Proves record id holds exactly `expected`, every element disclosed. The proof is prefixed with
+    // the record's encoding and padding ids (one byte each) so the verifier encodes `expected` the
+    // same way; the element count follows from `expected` itself.
+    fn prove_opens_to(&self, id: u64, expected: &[u8]) -> Result<Vec<u8>, ZkitError> {
+        let record = self.record(id)?;
+        // A based Delta record's elements depend on another record's bytes as well.
+        if delta_base(&record).is_some() {
+            return Err(ZkitError::UnsupportedEncoding(record.encoding));
+        }
+        let candidate = ingest_and_compress(expected.to_vec(), self.codec(record.encoding)?.as_ref(), record.padding);
+        if candidate.checksum != record.checksum {
+            return Err(ZkitError::ChecksumMismatch(id));
+        }
+        let mut instance = vec![record_hash(&record.data)];
+        instance.extend(&record.data);
+        let circuit = DisclosureCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+            disclosed: (0..record.data.len()).collect(),
+        };
+        let mut out = vec![record.encoding.id(), record.padding.id()];
+        out.extend(self.prove_statement(&circuit, &[&instance])?);
+        Ok(out)
+    }
+
+    // `commitment` is the record's record_commitment; the opened elements are re-derived from
+    // `expected` rather than taken from the proof.
+    fn verify_opens_to(&self, commitment: Fp, expected: &[u8], proof: &[u8]) -> Result<bool, ZkitError> {
+        if proof.len() < 2 {
+            return Err(ZkitError::MalformedProof);
+        }
+        let encoding = Encoding::from_id(proof[0]).ok_or(ZkitError::MalformedProof)?;
+        let padding = Padding::from_id(proof[1]).ok_or(ZkitError::MalformedProof)?;
+        let elements = ingest_and_compress(expected.to_vec(), self.codec(encoding)?.as_ref(), padding).data;
+        if record_hash(&elements) != commitment {
+            return Ok(false);
+        }
+        let mut instance = vec![commitment];
+        instance.extend(&elements);
+        let circuit = DisclosureCircuit {
+            values: vec![Value::unknown(); elements.len()],
+            disclosed: (0..elements.len()).collect(),
+        };
+        self.verify_statement(&circuit, &[&instance], &proof[2..])
+    }
+
+    // Commits to public inputs without revealing them: the proof's single public instance is
+    // record_hash(public_inputs), which is what verify_proof_committed is given. Same framing as
+    // prove_matches_hash, so only the input count is visible.
+    fn prove_committed(&self, public_inputs: &[Fp]) -> Result<Vec<u8>, ZkitError> {
+        let circuit = HashCircuit {
+            values: public_inputs.iter().map(|&x| Value::known(x)).collect(),
+        };
+        let mut out = (public_inputs.len() as u64).to_le_bytes().to_vec();
+        out.extend(self.prove_statement(&circuit, &[&[record_hash(public_inputs)]])?);
+        Ok(out)
+    }
+
+    fn verify_proof_committed(&self, public_input_hash: Fp, proof: &[u8]) -> Result<bool, ZkitError> {
+        self.verify_matches_hash(public_input_hash, proof)
+    }
+
+    // Discloses the elements at `disclosed` and proves they belong to the record whose record_hash
+    // is the public commitment. The proof carries the same u64 LE length prefix as prove_matches_hash.
+    fn prove_disclosed(&self, id: u64, disclosed: &[usize]) -> Result<(Vec<u8>, Vec<Fp>), ZkitError> {
+        let record = self.record(id)?;
+        if let Some(&index) = disclosed.iter().find(|&&idx| idx >= record.data.len()) {
+            return Err(ZkitError::IndexOutOfRange {
+                index,
+                len: record.data.len(),
+            });
+        }
+        let values: Vec<Fp> = disclosed.iter().map(|&idx| record.data[idx]).collect();
+        let mut instance = vec![record_hash(&record.data)];
+        instance.extend(&values);
+
+        let circuit = DisclosureCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+            disclosed: disclosed.to_vec(),
+        };
+        let mut out = (record.data.len() as u64).to_le_bytes().to_vec();
+        out.extend(self.prove_statement(&circuit, &[&instance])?);
+        Ok((out, values))
+    }
+
+    fn verify_disclosed(
+        &self,
+        commitment: Fp,
+        disclosed: &[usize],
+        values: &[Fp],
+        proof: &[u8],
+    ) -> Result<bool, ZkitError> {
+        if disclosed.len() != values.len() {
+            return Err(ZkitError::LengthMismatch);
+        }
+        let (len, proof) = self.split_len_prefix(proof)?;
+        if let Some(&index) = disclosed.iter().find(|&&idx| idx >= len) {
+            return Err(ZkitError::IndexOutOfRange { index, len });
+        }
+        let mut instance = vec![commitment];
+        instance.extend(values);
+
+        let circuit = DisclosureCircuit {
+            values: vec![Value::unknown(); len],
+            disclosed: disclosed.to_vec(),
+        };
+        self.verify_statement(&circuit, &[&instance], proof)
+    }
+
+    // Exposes only the record hash; verify_sorted recomputes it from the stored record.
+    fn prove_sorted(&self, id: u64) -> Result<Vec<u8>, ZkitError> {
+        let record = self.value_record(id)?;
+        if let Some(idx) = record.data.windows(2).position(|pair| !gap_in_range(pair[0], pair[1])) {
+            return Err(ZkitError::NotSorted { index: idx + 1 });
+        }
+        let circuit = SortedCircuit {
+            values: record.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        self.prove_statement(&circuit, &[&[record_hash(&record.data)]])
+    }
+
+    fn verify_sorted(&self, id: u64, proof: &[u8]) -> Result<bool, ZkitError> {
+        let record = self.record(id)?;
+        let circuit = SortedCircuit {
+            values: vec![Value::unknown(); record.data.len()],
+        };
+        self.verify_statement(&circuit, &[&[record_hash(&record.data)]], proof)
+    }
+
+    // A record's elements are the coefficients of a polynomial of degree < 2^k, committed with the
+    // instance params (IPA) and the fixed Blind::default(), which is Blind(1) rather than a random
+    // blind, so anyone holding the record derives the same commitment.
+    fn record_polynomial(&self, id: u64) -> Result<Polynomial<Fp, Coeff>, ZkitError> {
+        let record = self.record(id)?;
+        let domain = EvaluationDomain::<Fp>::new(1, self.params.k());
+        let mut poly = domain.empty_coeff();
+        if record.data.len() > poly.len() {
+            return Err(ZkitError::RecordTooLarge { limit: poly.len() });
+        }
+        poly[..record.data.len()].copy_from_slice(&record.data);
+        Ok(poly)
+    }
+
+    fn commit_record(&self, id: u64) -> Result<EqAffine, ZkitError> {
+        let poly = self.record_polynomial(id)?;
+        Ok(self.params.commit(&poly, Blind::default()).to_affine())
+    }
+
+    // Returns p(point) and an IPA opening proof for it against the record commitment.
+    fn open_record(&self, id: u64, point: Fp) -> Result<(Fp, Vec<u8>), ZkitError> {
+        let poly = self.record_polynomial(id)?;
+        let commitment = self.params.commit(&poly, Blind::default()).to_affine();
+        let value = eval_polynomial(&poly, point);
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        transcript.common_point(commitment)?;
+        transcript.common_scalar(point)?;
+        transcript.common_scalar(value)?;
+        commitment::create_proof(&self.params, self.proof_rng()?, &mut transcript, &poly, Blind::default(), point)?;
+        Ok((value, transcript.finalize()))
+    }
+
+    fn verify_opening(&self, id: u64, point: Fp, value: Fp, opening_proof: &[u8]) -> Result<bool, ZkitError> {
+        let commitment = self.commit_record(id)?;
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(opening_proof);
+        transcript.common_point(commitment)?;
+        transcript.common_scalar(point)?;
+        transcript.common_scalar(value)?;
+
+        let mut msm = self.params.empty_msm();
+        msm.append_term(Fp::one(), commitment);
+        match commitment::verify_proof(&self.params, msm, &mut transcript, point, value) {
+            Ok(guard) => Ok(guard.use_challenges().eval()),
+            Err(Error::Opening) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn record(&self, id: u64) -> Result<CompressedData, ZkitError> {
+        let storage = self.storage.read().unwrap();
+        storage.get(&id).cloned().ok_or(ZkitError::NotFound(id))
+    }
+
+    // A record for a statement about its values: sum, count, popcount, xor, bounds, order, UTF-8,
+    // schema, prefix and combined proofs. Length-prefixed and PKCS#7 padding put their bytes into
+    // the elements, where those statements would take them for data, so only zero padding is
+    // accepted. Hash, equality and disclosure proofs cover the stored elements as they are,
+    // padding included.
+    fn value_record(&self, id: u64) -> Result<CompressedData, ZkitError> {
+        let record = self.record(id)?;
+        if record.padding != Padding::Zero {
+            return Err(ZkitError::UnsupportedPadding(record.padding));
+        }
+        Ok(record)
+    }
+
+    fn prove_equal(&self, id_a: u64, id_b: u64) -> Result<Vec<u8>, ZkitError> {
+        let a = self.record(id_a)?;
+        let b = self.record(id_b)?;
+        if a.data.len() != b.data.len() {
+            return Err(ZkitError::LengthMismatch);
+        }
+        let circuit = EqualityCircuit {
+            a: a.data.iter().map(|&x| Value::known(x)).collect(),
+            b: b.data.iter().map(|&x| Value::known(x)).collect(),
+        };
+        let instance = [Fp::from(id_a), Fp::from(id_b), a.digest(), b.digest()];
+        self.prove_statement(&circuit, &[&instance])
+    }
+
+    // The instance is rebuilt from the verifier's own copies of both records, so a proof only
+    // passes if they are equal here too.
+    fn verify_equal(&self, id_a: u64, id_b: u64, proof: &[u8]) -> Result<bool, ZkitError> {
+        let a = self.record(id_a)?;
+        let b = self.record(id_b)?;
+        if a.data.len() != b.data.len() {
+            return Ok(false);
+        }
+        let circuit = EqualityCircuit {
+            a: vec![Value::unknown(); a.data.len()],
+            b: vec![Value::unknown(); b.data.len()],
+        };
+        let instance = [Fp::from(id_a), Fp::from(id_b), a.digest(), b.digest()];
+        self.verify_statement(&circuit, &[&instance], proof)
     }
-}
 
-fn ingest_and_compress(data: Vec<u8>) -> CompressedData {
-    let data_fp: Vec<Fp> = data.iter().map(|&x| Fp::from(x as u64)).collect();
-    let poly = Polynomial::from_vec(data_fp.clone());
-    let compressed_data = poly.to_vec();
-    CompressedData::new(compressed_data)
-}
+    fn retrieve_data(&self, id: RecordId) -> Option<Vec<u8>> {
+        self.retrieve_with(id.0, self.decode_mode).ok()
+    }
 
-struct ExampleCircuit<F: FieldExt> {
-    pub data: Vec<F>,
-    _marker: PhantomData<F>,
-}
+    #[deprecated(note = "pass a RecordId to retrieve_data")]
+    fn retrieve_data_u64(&self, index: u64) -> Option<Vec<u8>> {
+        self.retrieve_data(RecordId(index))
+    }
 
-impl<F: FieldExt> Circuit<F> for ExampleCircuit<F> {
-    type Config = ExampleConfig;
-    type FloorPlanner = SimpleFloorPlanner;
+    fn register_codec(&self, codec: Arc<dyn Codec>) -> Result<Encoding, ZkitError> {
+        let id = codec.id();
+        if id < CUSTOM_CODEC_MIN {
+            return Err(ZkitError::ReservedCodecId(id));
+        }
+        self.codecs.write().unwrap().insert(id, codec);
+        Ok(Encoding::Custom(id))
+    }
 
-    fn without_witnesses(&self) -> Self {
-        Self {
-            data: vec![],
-            _marker: PhantomData,
+    fn codec(&self, encoding: Encoding) -> Result<Arc<dyn Codec>, ZkitError> {
+        match encoding {
+            Encoding::Custom(id) => self.codecs.read().unwrap().get(&id).cloned().ok_or(ZkitError::UnknownCodec(id)),
+            builtin => Ok(Arc::new(builtin)),
         }
     }
 
-    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let input = meta.advice_column();
-        let s = meta.selector();
+    fn retrieve_checked(&self, id: u64) -> Result<Vec<u8>, ZkitError> {
+        self.retrieve_with(id, DecodeMode::Strict)
+    }
 
-        meta.create_gate("data processing", |v_cells| {
-            let input_exp = v_cells.query_advice(input, Rotation::cur());
-            let s = v_cells.query_selector(s);
+    // Overrides the instance's decode mode for one call.
+    fn retrieve_with(&self, id: u64, mode: DecodeMode) -> Result<Vec<u8>, ZkitError> {
+        self.log_access(AccessKind::Retrieve, Some(id));
+        if let Some(published) = &self.published {
+            let view = published.load();
+            let record = view.get(&id).ok_or(ZkitError::NotFound(id))?;
+            return self.decode_record_with(record, &view, mode);
+        }
+        let storage = self.storage.read().unwrap();
+        let record = storage.get(&id).ok_or(ZkitError::NotFound(id))?;
+        self.decode_record_with(record, &storage, mode)
+    }
 
-            vec![s * input_exp]
-        });
+    // Called with the storage write lock held, so copies are published in write order.
+    fn publish(&self, storage: &HashMap<u64, CompressedData>) {
+        if let Some(published) = &self.published {
+            published.store(Arc::new(storage.clone()));
+        }
+    }
 
-        ExampleConfig {
-            input,
-            s,
+    // `storage` is the store the record came from; Delta records find their bases in it.
+    fn decode_record(&self, record: &CompressedData, storage: &HashMap<u64, CompressedData>) -> Result<Vec<u8>, ZkitError> {
+        self.decode_record_with(record, storage, self.decode_mode)
+    }
+
+    fn decode_record_with(
+        &self,
+        record: &CompressedData,
+        storage: &HashMap<u64, CompressedData>,
+        mode: DecodeMode,
+    ) -> Result<Vec<u8>, ZkitError> {
+        if record.encoding == Encoding::Delta {
+            return self.decode_delta(record, storage, mode);
         }
+        if mode == DecodeMode::Strict {
+            check_elements(record.encoding, &record.data)?;
+        }
+        let bytes = self.codec(record.encoding)?.decompress(&record.data);
+        record.padding.strip(bytes, record.orig_len, element_bytes(record.encoding))
     }
 
-    fn synthesize(
+    // Walks back to the first base that isn't a Delta record, then applies the deltas forward. A
+    // chain longer than the store can only be a cycle, which ids from insert_with_id or a
+    // restored snapshot could form.
+    fn decode_delta(
         &self,
-        config: Self::Config,
-        mut layouter: impl Layouter<F>,
-    ) -> Result<(), Error> {
-        layouter.assign_region(
-            || "data processing",
-            |mut region| {
-                config.s.enable(&mut region, 0)?;
+        record: &CompressedData,
+        storage: &HashMap<u64, CompressedData>,
+        mode: DecodeMode,
+    ) -> Result<Vec<u8>, ZkitError> {
+        let mut chain = vec![record];
+        let mut bytes = Vec::new();
+        let mut base = delta_base(record);
+        while let Some(id) = base {
+            let parent = storage.get(&id).ok_or(ZkitError::NotFound(id))?;
+            if parent.encoding != Encoding::Delta {
+                bytes = self.decode_record_with(parent, storage, mode)?;
+                break;
+            }
+            if chain.len() > storage.len() {
+                return Err(ZkitError::CorruptSnapshot);
+            }
+            chain.push(parent);
+            base = delta_base(parent);
+        }
+        for link in chain.into_iter().rev() {
+            let stream = apply_deltas(&bytes, link.data.get(1..).unwrap_or_default(), mode)?;
+            bytes = link.padding.strip(stream, link.orig_len, 1)?;
+        }
+        Ok(bytes)
+    }
 
-                for (idx, &value) in self.data.iter().enumerate() {
-                    region.assign_advice(|| "input", config.input, idx, || Value::known(value))?;
+    fn decoded(&self, id: u64) -> Result<Vec<u8>, ZkitError> {
+        let storage = self.storage.read().unwrap();
+        let record = storage.get(&id).ok_or(ZkitError::NotFound(id))?;
+        self.decode_record(record, &storage)
+    }
+
+    fn set_metadata(&self, id: u64, key: &str, value: &str) -> Result<(), ZkitError> {
+        let mut storage = self.storage.write().unwrap();
+        let record = storage.get_mut(&id).ok_or(ZkitError::NotFound(id))?;
+        if self.wal.is_some() {
+            let mut updated = record.clone();
+            updated.metadata.insert(key.to_string(), value.to_string());
+            self.wal_put(id, &updated, *self.zkio_counter.lock().unwrap())?;
+        }
+        record.metadata.insert(key.to_string(), value.to_string());
+        self.publish(&storage);
+        Ok(())
+    }
+
+    // Stores the decoded bytes of `ids`, in the order given, as one new record. The byte length of
+    // each part goes into MERGE_BOUNDARIES_KEY so split can undo it. Originals are forgotten only
+    // once the merged record exists.
+    fn merge(&self, ids: &[RecordId], remove_originals: bool) -> Result<RecordId, ZkitError> {
+        let mut merged = Vec::new();
+        let mut lengths = Vec::with_capacity(ids.len());
+        for &RecordId(id) in ids {
+            let bytes = self.decoded(id)?;
+            lengths.push(bytes.len().to_string());
+            merged.extend(bytes);
+        }
+        let merged_id = self.batch_and_inscribe(merged)?;
+        self.set_metadata(merged_id.0, MERGE_BOUNDARIES_KEY, &lengths.join(","))?;
+        if remove_originals {
+            for &id in ids {
+                self.forget(id);
+            }
+        }
+        Ok(merged_id)
+    }
+
+    // Splits a merged record back into one new record per part; the merged record is kept.
+    fn split(&self, id: RecordId) -> Result<Vec<RecordId>, ZkitError> {
+        let RecordId(id) = id;
+        let boundaries = self.get_metadata(id, MERGE_BOUNDARIES_KEY)?.ok_or(ZkitError::NotMerged(id))?;
+        let lengths = boundaries
+            .split(',')
+            .map(|len| len.parse::<usize>().map_err(|_| ZkitError::NotMerged(id)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let bytes = self.decoded(id)?;
+        if lengths.iter().try_fold(0usize, |total, &len| total.checked_add(len)) != Some(bytes.len()) {
+            return Err(ZkitError::NotMerged(id));
+        }
+        let mut rest = &bytes[..];
+        let mut parts = Vec::with_capacity(lengths.len());
+        for len in lengths {
+            let (part, tail) = rest.split_at(len);
+            parts.push(self.batch_and_inscribe(part.to_vec())?);
+            rest = tail;
+        }
+        Ok(parts)
+    }
+
+    fn get_metadata(&self, id: u64, key: &str) -> Result<Option<String>, ZkitError> {
+        let storage = self.storage.read().unwrap();
+        let record = storage.get(&id).ok_or(ZkitError::NotFound(id))?;
+        Ok(record.metadata.get(key).cloned())
+    }
+
+    // The whole store as JSON, records ordered by id, for tooling that can't read snapshots.
+    fn export_json(&self) -> Result<String, ZkitError> {
+        let storage = self.storage.read().unwrap();
+        let mut records: Vec<RecordExport> = storage
+            .iter()
+            .map(|(&id, record)| RecordExport {
+                id,
+                encoding: record.encoding,
+                orig_len: record.orig_len,
+                data: record.data.clone(),
+                metadata: record.metadata.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            })
+            .collect();
+        records.sort_unstable_by_key(|record| record.id);
+        Ok(serde_json::to_string_pretty(&records).map_err(io::Error::from)?)
+    }
+
+    fn records_since(&self, after_id: u64) -> Vec<(u64, Vec<u8>)> {
+        let storage = self.storage.read().unwrap();
+        let mut records: Vec<(u64, Vec<u8>)> = storage
+            .iter()
+            .filter(|(&id, _)| id > after_id)
+            .filter_map(|(&id, record)| Some((id, self.decode_record(record, &storage).ok()?)))
+            .collect();
+        records.sort_unstable_by_key(|(id, _)| *id);
+        records
+    }
+
+    fn reencode(&self, id: u64, to: Encoding) -> Result<(), ZkitError> {
+        let mut storage = self.storage.write().unwrap();
+        let record = storage.get(&id).ok_or(ZkitError::NotFound(id))?;
+        if record.encoding != to {
+            let bytes = self.decode_record(record, &storage)?;
+            let mut reencoded = ingest_and_compress(bytes, self.codec(to)?.as_ref(), record.padding);
+            reencoded.metadata = record.metadata.clone();
+            self.wal_put(id, &reencoded, *self.zkio_counter.lock().unwrap())?;
+            storage.insert(id, reencoded);
+            self.publish(&storage);
+            drop(storage);
+            self.invalidate_proof(id);
+        }
+        Ok(())
+    }
+
+    // Re-encodes every record to `to`. With `reprove`, records that had cached proofs get them
+    // regenerated against the same public inputs; a failed reprove leaves no cached proof.
+    fn migrate(&self, to: Encoding, reprove: bool) -> MigrationReport {
+        let mut cached: HashMap<u64, Vec<Vec<Fp>>> = HashMap::new();
+        if reprove {
+            for (&(id, _), entry) in self.proof_cache.read().unwrap().iter() {
+                cached.entry(id).or_default().push(entry.public_inputs.clone());
+            }
+        }
+        let mut ids: Vec<u64> = self.storage.read().unwrap().keys().copied().collect();
+        ids.sort_unstable();
+
+        let results = ids
+            .into_iter()
+            .map(|id| {
+                let result = self.reencode(id, to).and_then(|()| {
+                    cached
+                        .get(&id)
+                        .into_iter()
+                        .flatten()
+                        .try_for_each(|inputs| self.prove_and_cache(id, inputs).map(|_| ()))
+                });
+                (id, result)
+            })
+            .collect();
+        MigrationReport { results }
+    }
+
+    // Proves every stored record (with no public inputs) in id order, appending each id to
+    // progress_file once its proof is cached. Ids already listed are skipped, so after a crash or
+    // an error a rerun picks up where the last one stopped. The proofs themselves only outlive
+    // the process with a proof_cache_dir. Only newline-terminated lines count, so a torn last
+    // line, which could parse as a different id, is ignored and that record re-proved.
+    fn prove_all_resumable(&self, progress_file: &Path) -> Result<(), ZkitError> {
+        let text = match fs::read_to_string(progress_file) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines: Vec<&str> = text.split('\n').collect();
+        let torn = lines.pop().unwrap_or("");
+        let done: HashSet<u64> = lines.iter().filter_map(|line| line.parse().ok()).collect();
+        let mut progress = fs::OpenOptions::new().create(true).append(true).open(progress_file)?;
+        // The torn piece is cut off rather than terminated, or the next run would read it as done.
+        if !torn.is_empty() {
+            progress.set_len((text.len() - torn.len()) as u64)?;
+            progress.sync_data()?;
+        }
+
+        let mut ids: Vec<u64> = self.storage.read().unwrap().keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids.into_iter().filter(|id| !done.contains(id)) {
+            self.prove(id, &[])?;
+            writeln!(progress, "{}", id)?;
+            progress.sync_data()?;
+        }
+        Ok(())
+    }
+
+    #[deprecated(note = "pass a RecordId to forget")]
+    fn forget_u64(&self, id: u64) -> bool {
+        self.forget(RecordId(id))
+    }
+
+    fn forget(&self, id: RecordId) -> bool {
+        let RecordId(id) = id;
+        let mut storage = self.storage.write().unwrap();
+        // Delta records built on this one are rewritten as Bytewise first, while their base can
+        // still be read. If any of them can't be decoded or logged, nothing is forgotten: the
+        // base stays so they stay readable, and false is returned.
+        let dependents: Vec<u64> = storage.iter().filter(|(_, r)| delta_base(r) == Some(id)).map(|(&d, _)| d).collect();
+        let mut materialized = Vec::new();
+        for dependent in dependents {
+            let record = &storage[&dependent];
+            let Ok(bytes) = self.decode_record(record, &storage) else {
+                return false;
+            };
+            let mut rewritten = ingest_and_compress(bytes, &Encoding::Bytewise, record.padding);
+            rewritten.metadata = record.metadata.clone();
+            materialized.push((dependent, rewritten));
+        }
+        let counter = *self.zkio_counter.lock().unwrap();
+        for (dependent, record) in &materialized {
+            if let Err(e) = self.wal_put(*dependent, record, counter) {
+                drop(storage);
+                self.emit(ZkitEvent::WalWriteFailed { id: *dependent, reason: e.to_string() });
+                return false;
+            }
+        }
+        if !storage.contains_key(&id) {
+            return false;
+        }
+        let rewritten: Vec<u64> = materialized.iter().map(|(dependent, _)| *dependent).collect();
+        storage.extend(materialized);
+        match storage.remove(&id) {
+            Some(mut record) => {
+                record.zeroize();
+                // The record is wiped regardless; a log that missed the delete is reported instead.
+                let logged = self.wal_delete(id);
+                self.publish(&storage);
+                drop(storage);
+                self.invalidate_proof(id);
+                for dependent in rewritten {
+                    self.invalidate_proof(dependent);
                 }
+                if let Err(e) = logged {
+                    self.emit(ZkitEvent::WalWriteFailed { id, reason: e.to_string() });
+                }
+                true
+            }
+            None => false,
+        }
+    }
 
-                Ok(())
-            },
-        )?;
+    fn snapshot(&self, path: &Path) -> Result<(), ZkitError> {
+        let storage = self.storage.read().unwrap();
+        let counter = *self.zkio_counter.lock().unwrap();
+        self.write_snapshot(&storage, counter, path)
+    }
+
+    fn write_snapshot(&self, storage: &HashMap<u64, CompressedData>, counter: u64, path: &Path) -> Result<(), ZkitError> {
+        let mut out = io::BufWriter::new(File::create(path)?);
+        out.write_all(&SNAPSHOT_MAGIC)?;
+        out.write_all(&[SNAPSHOT_VERSION])?;
+        out.write_all(&counter.to_le_bytes())?;
+        out.write_all(&(storage.len() as u64).to_le_bytes())?;
+
+        let mut ids: Vec<u64> = storage.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            write_snapshot_record(&mut out, id, &storage[&id], self.cipher.as_ref())?;
+        }
+        out.flush()?;
+        if self.fsync_snapshots {
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            sync_parent_dir(path)?;
+        }
+        Ok(())
+    }
+
+    // Opens (or creates) a write-ahead log that every later write appends to. Restoring a base
+    // snapshot and then calling replay_wal on the log rebuilds the store.
+    fn attach_wal(&mut self, path: &Path) -> Result<(), ZkitError> {
+        let mut file = fs::OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&WAL_MAGIC)?;
+            file.write_all(&[SNAPSHOT_VERSION])?;
+            file.sync_all()?;
+        } else {
+            let mut header = [0u8; WAL_HEADER_LEN as usize];
+            file.read_exact(&mut header)?;
+            if header[..4] != WAL_MAGIC {
+                return Err(ZkitError::CorruptSnapshot);
+            }
+            match header[4] {
+                SNAPSHOT_VERSION => {}
+                version @ 1..=SNAPSHOT_VERSION => {
+                    drop(file);
+                    self.upgrade_wal(path, version)?;
+                    file = fs::OpenOptions::new().read(true).append(true).open(path)?;
+                }
+                version => return Err(ZkitError::UnsupportedVersion(version)),
+            }
+        }
+        self.wal = Some(Mutex::new(file));
+        Ok(())
+    }
+
+    // New entries are always written in the current record format, so a log from an older
+    // version is rewritten in it first, through a temp file and a rename. A torn last entry is
+    // dropped as replay_wal would.
+    fn upgrade_wal(&self, path: &Path, version: u8) -> Result<(), ZkitError> {
+        let bytes = fs::read(path)?;
+        let mut input = &bytes[WAL_HEADER_LEN as usize..];
+        let mut out = WAL_MAGIC.to_vec();
+        out.push(SNAPSHOT_VERSION);
+        while !input.is_empty() {
+            let (id, record, counter) = match read_wal_entry(&mut input, version, self.cipher.as_ref()) {
+                Ok(entry) => entry,
+                Err(_) if input.is_empty() => break,
+                Err(e) => return Err(e),
+            };
+            match record {
+                Some(record) => {
+                    out.push(WAL_PUT);
+                    out.extend_from_slice(&counter.to_le_bytes());
+                    write_snapshot_record(&mut out, id, &record, self.cipher.as_ref())?;
+                }
+                None => {
+                    out.push(WAL_DELETE);
+                    out.extend_from_slice(&id.to_le_bytes());
+                }
+            }
+        }
+        let tmp = path.with_extension("wal.upgrade");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&out)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        out.zeroize();
+        Ok(sync_parent_dir(path)?)
+    }
+
+    fn wal_append(&self, entry: &[u8]) -> Result<(), ZkitError> {
+        if let Some(wal) = &self.wal {
+            let mut file = wal.lock().unwrap();
+            file.write_all(entry)?;
+            if self.fsync_snapshots {
+                file.sync_data()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn wal_put(&self, id: u64, record: &CompressedData, counter: u64) -> Result<(), ZkitError> {
+        if self.wal.is_none() {
+            return Ok(());
+        }
+        let mut entry = vec![WAL_PUT];
+        entry.extend_from_slice(&counter.to_le_bytes());
+        write_snapshot_record(&mut entry, id, record, self.cipher.as_ref())?;
+        self.wal_append(&entry)
+    }
+
+    fn wal_delete(&self, id: u64) -> Result<(), ZkitError> {
+        if self.wal.is_none() {
+            return Ok(());
+        }
+        let mut entry = vec![WAL_DELETE];
+        entry.extend_from_slice(&id.to_le_bytes());
+        self.wal_append(&entry)
+    }
+
+    // Applies a log's entries on top of the current store and returns how many were applied. An
+    // entry cut short at the end of the file, as a crash mid-append leaves it, is ignored.
+    fn replay_wal(&self, path: &Path) -> Result<usize, ZkitError> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < WAL_HEADER_LEN as usize || bytes[..4] != WAL_MAGIC {
+            return Err(ZkitError::CorruptSnapshot);
+        }
+        let version = bytes[4];
+        if version == 0 || version > SNAPSHOT_VERSION {
+            return Err(ZkitError::UnsupportedVersion(version));
+        }
+
+        let mut storage = self.storage.write().unwrap();
+        let mut zkio_counter = self.zkio_counter.lock().unwrap();
+        let mut input = &bytes[WAL_HEADER_LEN as usize..];
+        let mut applied = 0;
+        while !input.is_empty() {
+            let (id, record, counter) = match read_wal_entry(&mut input, version, self.cipher.as_ref()) {
+                Ok(entry) => entry,
+                Err(ZkitError::DecryptionFailed) => return Err(ZkitError::DecryptionFailed),
+                Err(_) if input.is_empty() => break,
+                Err(e) => return Err(e),
+            };
+            match record {
+                Some(record) => {
+                    storage.insert(id, record);
+                    *zkio_counter = (*zkio_counter).max(counter);
+                }
+                None => {
+                    if let Some(mut record) = storage.remove(&id) {
+                        record.zeroize();
+                    }
+                }
+            }
+            applied += 1;
+        }
+        self.publish(&storage);
+        self.proof_cache.write().unwrap().clear();
+        Ok(applied)
+    }
+
+    // Writes a base snapshot and empties the log, holding the store read lock throughout so no
+    // write lands between the two.
+    fn compact_wal(&self, snapshot_path: &Path) -> Result<(), ZkitError> {
+        let storage = self.storage.read().unwrap();
+        let counter = *self.zkio_counter.lock().unwrap();
+        self.write_snapshot(&storage, counter, snapshot_path)?;
+        if let Some(wal) = &self.wal {
+            let file = wal.lock().unwrap();
+            file.set_len(WAL_HEADER_LEN)?;
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    // Writes every record's decoded bytes, sorted by id. Unlike a snapshot it keeps neither the
+    // encoding, padding nor metadata.
+    fn export_all_bytes(&self, path: &Path) -> io::Result<()> {
+        let storage = self.storage.read().unwrap();
+        let mut ids: Vec<u64> = storage.keys().copied().collect();
+        ids.sort_unstable();
+        let mut out = io::BufWriter::new(File::create(path)?);
+        out.write_all(&BYTES_EXPORT_MAGIC)?;
+        out.write_all(&(ids.len() as u64).to_le_bytes())?;
+        for id in ids {
+            let bytes = self
+                .decode_record(&storage[&id], &storage)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("record {}: {}", id, e)))?;
+            out.write_all(&id.to_le_bytes())?;
+            out.write_all(&(bytes.len() as u64).to_le_bytes())?;
+            out.write_all(&bytes)?;
+        }
+        out.flush()
+    }
+
+    // Re-ingests an export_all_bytes file under its original ids, with this instance's encoding and
+    // padding, and moves the counter past the highest. Nothing is stored if any id is already taken
+    // or appears twice in the file, or if logging the records to the WAL fails.
+    fn import_all_bytes(&self, path: &Path) -> Result<Vec<u64>, ZkitError> {
+        let mut input = io::BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if magic != BYTES_EXPORT_MAGIC {
+            return Err(ZkitError::CorruptSnapshot);
+        }
+        let count = read_u64(&mut input)?;
+        let codec = self.codec(self.encoding)?;
+        let mut records = Vec::new();
+        let mut seen = HashSet::new();
+        for _ in 0..count {
+            let id = read_u64(&mut input)?;
+            if !seen.insert(id) {
+                return Err(ZkitError::IdInUse(id));
+            }
+            let len = read_u64(&mut input)?;
+            self.check_record_size(len)?;
+            // The length comes from the file, so the read grows the buffer rather than trusting it.
+            let mut bytes = Vec::new();
+            if (&mut input).take(len).read_to_end(&mut bytes)? as u64 != len {
+                return Err(ZkitError::CorruptSnapshot);
+            }
+            records.push((id, ingest_and_compress(bytes, codec.as_ref(), self.padding)));
+        }
+
+        let ids: Vec<u64> = records.iter().map(|(id, _)| *id).collect();
+        {
+            let mut storage = self.storage.write().unwrap();
+            let mut zkio_counter = self.zkio_counter.lock().unwrap();
+            if let Some(&id) = ids.iter().find(|id| storage.contains_key(id)) {
+                return Err(ZkitError::IdInUse(id));
+            }
+            let counter = ids.iter().copied().fold(*zkio_counter, u64::max);
+            for (id, record) in &records {
+                self.wal_put(*id, record, counter)?;
+            }
+            storage.extend(records);
+            *zkio_counter = counter;
+            self.publish(&storage);
+        }
+        for &id in &ids {
+            self.emit(ZkitEvent::Ingested(id));
+        }
+        Ok(ids)
+    }
 
+    // Replaces the current store and counter. Checksums are loaded as written, not recomputed,
+    // so verify_snapshot_integrity can catch records that were altered on disk.
+    fn restore(&self, path: &Path) -> Result<(), ZkitError> {
+        let mut input = io::BufReader::new(File::open(path)?);
+        let (version, counter, count) = read_snapshot_header(&mut input)?;
+
+        let mut records = HashMap::new();
+        for _ in 0..count {
+            let (id, record) = read_snapshot_record(&mut input, version, self.cipher.as_ref())?;
+            records.insert(id, record);
+        }
+
+        let mut storage = self.storage.write().unwrap();
+        let mut zkio_counter = self.zkio_counter.lock().unwrap();
+        *storage = records;
+        *zkio_counter = counter;
+        self.publish(&storage);
+        self.proof_cache.write().unwrap().clear();
         Ok(())
     }
+
+    fn verify_snapshot_integrity(&self) -> Result<(), Vec<(u64, ZkitError)>> {
+        let storage = self.storage.read().unwrap();
+        let mut failures: Vec<(u64, ZkitError)> = storage
+            .iter()
+            .filter(|(_, record)| record.checksum != record_checksum(&record.data))
+            .map(|(&id, _)| (id, ZkitError::ChecksumMismatch(id)))
+            .collect();
+        if failures.is_empty() {
+            return Ok(());
+        }
+        failures.sort_by_key(|(id, _)| *id);
+        Err(failures)
+    }
+
+    fn status(&self) -> Status {
+        let stats = self.stats();
+        Status {
+            k: self.params.k(),
+            keys_ready: self.keys.read().unwrap().is_some(),
+            record_count: stats.record_count,
+            total_bytes: stats.total_original_bytes,
+            vk_fingerprint: self.vk_fingerprint(),
+            cached_proofs: self.proof_cache.read().unwrap().len(),
+        }
+    }
+
+    fn stats(&self) -> StoreStats {
+        let storage = self.storage.read().unwrap();
+        StoreStats {
+            record_count: storage.len(),
+            total_original_bytes: storage.values().map(|d| d.orig_len).sum(),
+            total_field_elements: storage.values().map(|d| d.data.len()).sum(),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
-struct ExampleConfig {
-    input: Column<Advice>,
-    s: Selector,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Plain,
+    // One JSON object per result on stdout; menu and prompts move to stderr.
+    Json,
 }
 
-struct ZKIT {
-    storage: Mutex<HashMap<u64, CompressedData>>,
-    zkio_counter: Mutex<u64>,
-    params: Params<Fp>,
-    vk: Option<VerifyingKey<Fp>>,
-    pk: Option<ProvingKey<Fp>>,
+impl std::str::FromStr for OutputFormat {
+    type Err = ZkitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(ZkitError::Config(format!("unknown output format {:?}; use json or plain", other))),
+        }
+    }
 }
 
-impl ZKIT {
-    fn new(params: Params<Fp>) -> Self {
-        Self {
-            storage: Mutex::new(HashMap::new()),
-            zkio_counter: Mutex::new(0),
-            params,
-            vk: None,
-            pk: None,
+// Everything a CLI command reports. Display is the plain form; the JSON form is tagged by "event".
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum CliOutput {
+    Ingested { id: u64 },
+    ProofCreated { proof: String },
+    ProofCancelled,
+    Verified { ok: bool },
+    Retrieved { id: u64, data: Vec<u8> },
+    NotFound { id: u64 },
+    Status {
+        k: u32,
+        keys_ready: bool,
+        record_count: usize,
+        total_bytes: usize,
+        vk_fingerprint: Option<String>,
+        cached_proofs: usize,
+        #[serde(skip)]
+        text: String,
+    },
+    Loadtest {
+        cycles: usize,
+        verified: usize,
+        failed: usize,
+        elapsed_ms: f64,
+        p50_ms: f64,
+        p90_ms: f64,
+        p99_ms: f64,
+        #[serde(skip)]
+        text: String,
+    },
+    VerifiedAll {
+        passed: usize,
+        failed: Vec<u64>,
+        errors: Vec<(u64, String)>,
+    },
+    InvalidInput { message: String },
+    Error { command: String, message: String },
+}
+
+impl std::fmt::Display for CliOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliOutput::Ingested { id } => write!(f, "Data ingested with ID: {}", id),
+            CliOutput::ProofCreated { proof } => write!(f, "Proof created successfully: {}", proof),
+            CliOutput::ProofCancelled => write!(f, "Proof cancelled."),
+            CliOutput::Verified { ok: true } => write!(f, "Proof verified successfully."),
+            CliOutput::Verified { ok: false } => write!(f, "Proof verification failed."),
+            CliOutput::Retrieved { data, .. } => write!(f, "Retrieved data: {:?}", data),
+            CliOutput::NotFound { .. } => write!(f, "Data not found."),
+            CliOutput::Status { text, .. } | CliOutput::Loadtest { text, .. } => write!(f, "{}", text),
+            CliOutput::VerifiedAll { passed: 0, failed, errors } if failed.is_empty() && errors.is_empty() => {
+                write!(f, "No cached proofs to verify; Create Proof caches the proofs it makes.")
+            }
+            CliOutput::VerifiedAll { passed, failed, errors } => {
+                write!(f, "Verified cached proofs: {} passed, {} failed, {} errors.", passed, failed.len(), errors.len())?;
+                for id in failed {
+                    write!(f, "\n  record {}: verification failed", id)?;
+                }
+                for (id, message) in errors {
+                    write!(f, "\n  record {}: {}", id, message)?;
+                }
+                Ok(())
+            }
+            CliOutput::InvalidInput { message } => write!(f, "{}", message),
+            CliOutput::Error { command, message } => write!(f, "Could not {}: {}", command, message),
         }
     }
+}
 
-    fn setup_keys(&mut self, circuit: &impl Circuit<Fp>) -> Result<(), Error> {
-        let vk = keygen_vk(&self.params, circuit)?;
-        let pk = keygen_pk(&self.params, vk.clone(), circuit)?;
-        self.vk = Some(vk);
-        self.pk = Some(pk);
-        Ok(())
+impl From<Status> for CliOutput {
+    fn from(status: Status) -> Self {
+        CliOutput::Status {
+            text: status.to_string(),
+            k: status.k,
+            keys_ready: status.keys_ready,
+            record_count: status.record_count,
+            total_bytes: status.total_bytes,
+            vk_fingerprint: status.vk_fingerprint.map(hex::encode),
+            cached_proofs: status.cached_proofs,
+        }
     }
+}
 
-    fn batch_and_inscribe(&self, data: Vec<u8>) -> u64 {
-        let compressed_data = ingest_and_compress(data);
-        let mut storage = self.storage.lock().unwrap();
-        let mut zkio_counter = self.zkio_counter.lock().unwrap();
-        *zkio_counter += 1;
-        storage.insert(*zkio_counter, compressed_data);
-        *zkio_counter
+impl From<LoadtestReport> for CliOutput {
+    fn from(report: LoadtestReport) -> Self {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        CliOutput::Loadtest {
+            text: report.to_string(),
+            cycles: report.cycles,
+            verified: report.verified,
+            failed: report.failed,
+            elapsed_ms: ms(report.elapsed),
+            p50_ms: ms(report.p50),
+            p90_ms: ms(report.p90),
+            p99_ms: ms(report.p99),
+        }
     }
+}
 
-    fn create_proof(&self, circuit: &impl Circuit<Fp>) -> Result<Vec<u8>, Error> {
-        let pk = self.pk.as_ref().expect("ProvingKey not set up");
-        let mut transcript = Vec::new();
-        create_proof(
-            &self.params,
-            pk,
-            &[circuit],
-            &[&[]],
-            OsRng,
-            &mut transcript,
-        )?;
-        Ok(transcript)
+// All CLI output goes through here. Results go to stdout in the chosen format; menus, prompts and
+// progress are for a person, so in JSON mode they go to stderr and stdout stays parseable.
+struct Cli {
+    format: OutputFormat,
+}
+
+impl Cli {
+    fn result(&self, output: CliOutput) {
+        match self.format {
+            OutputFormat::Plain => println!("{}", output),
+            OutputFormat::Json => println!("{}", serde_json::to_string(&output).expect("CLI output always serializes")),
+        }
     }
 
-    fn verify_proof(&self, proof: &[u8]) -> Result<bool, Error> {
-        let vk = self.vk.as_ref().expect("VerifyingKey not set up");
-        let mut transcript = proof.to_vec();
-        verify_proof(&self.params, vk, &[&[]], &mut transcript)?;
-        Ok(true)
+    fn line(&self, text: &str) {
+        match self.format {
+            OutputFormat::Plain => println!("{}", text),
+            OutputFormat::Json => eprintln!("{}", text),
+        }
+    }
+
+    fn prompt(&self, text: &str) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Plain => {
+                print!("{}", text);
+                io::stdout().flush()
+            }
+            OutputFormat::Json => {
+                eprint!("{}", text);
+                io::stderr().flush()
+            }
+        }
+    }
+
+    fn error(&self, command: &str, e: impl std::fmt::Display) {
+        self.result(CliOutput::Error {
+            command: command.to_string(),
+            message: e.to_string(),
+        });
     }
 
-    fn retrieve_data(&self, index: u64) -> Option<Vec<u8>> {
-        let storage = self.storage.lock().unwrap();
-        storage.get(&index).map(|d| d.data.iter().map(|&fp| fp.get_lower_32() as u8).collect())
+    fn invalid(&self, message: &str) {
+        self.result(CliOutput::InvalidInput { message: message.to_string() });
     }
 }
 
+// Ctrl-C cancels a running proof; at the menu it exits as usual.
+static PROVING: AtomicBool = AtomicBool::new(false);
+static CANCEL_PROOF: AtomicBool = AtomicBool::new(false);
+
 fn main() {
-    let params: Params<Fp> = Params::new(1 << 8);
-    let mut zkit = ZKIT::new(params);
+    if let Err(e) = run() {
+        eprintln!("zkit: {}", e);
+        std::process::exit(1);
+    }
+}
+
+// Errors from a single menu command are reported and the session goes on; setup and terminal
+// I/O failures end it through main.
+fn run() -> Result<(), ZkitError> {
+    let zkit = Arc::new(ZKIT::with_k(DEFAULT_K)?);
 
     // Setup keys with an example circuit
-    let example_circuit = ExampleCircuit {
+    let example_circuit: ExampleCircuit<Fp> = ExampleCircuit {
         data: vec![Fp::from(1), Fp::from(2), Fp::from(3)],
+        lookup: None,
         _marker: PhantomData,
     };
-    zkit.setup_keys(&example_circuit).unwrap();
+    zkit.setup_keys(&example_circuit, false)?;
+
+    let args: Vec<String> = std::env::args().collect();
+    let format = match args.iter().position(|arg| arg == "--format") {
+        Some(pos) => args
+            .get(pos + 1)
+            .ok_or_else(|| ZkitError::Config("--format needs json or plain".to_string()))?
+            .parse()?,
+        None => OutputFormat::Plain,
+    };
+    let cli = Cli { format };
+    if let Some(pos) = args.iter().position(|arg| arg == "--loadtest") {
+        let n = args
+            .get(pos + 1)
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| ZkitError::Config("--loadtest needs a cycle count".to_string()))?;
+        cli.result(zkit.loadtest(n).into());
+        return Ok(());
+    }
+
+    ctrlc::set_handler(|| {
+        if PROVING.load(Ordering::SeqCst) {
+            CANCEL_PROOF.store(true, Ordering::SeqCst);
+        } else {
+            std::process::exit(130);
+        }
+    })
+    .map_err(|e| ZkitError::Config(format!("failed to install Ctrl-C handler: {}", e)))?;
 
     loop {
-        println!("ZKIT Blockchain Simulation");
-        println!("1. Ingest Data");
-        println!("2. Create Proof");
-        println!("3. Verify Proof");
-        println!("4. Retrieve Data");
-        println!("5. Exit");
-        print!("Enter your choice: ");
-        io::stdout().flush().unwrap();
+        cli.line("ZKIT Blockchain Simulation");
+        cli.line("1. Ingest Data");
+        cli.line("2. Create Proof");
+        cli.line("3. Verify Proof");
+        cli.line("4. Retrieve Data");
+        cli.line("5. Status");
+        cli.line("6. Exit");
+        cli.line("7. Verify All");
+        cli.prompt("Enter your choice: ")?;
 
         let mut choice = String::new();
-        io::stdin().read_line(&mut choice).unwrap();
-        let choice: u32 = choice.trim().parse().unwrap();
+        // EOF on stdin ends the session instead of spinning on empty input.
+        if io::stdin().read_line(&mut choice)? == 0 {
+            break;
+        }
+        let choice: u32 = match choice.trim().parse() {
+            Ok(choice) => choice,
+            Err(_) => {
+                cli.invalid("Invalid choice, please try again.");
+                continue;
+            }
+        };
 
         match choice {
             1 => {
                 let mut data = String::new();
-                print!("Enter data to ingest (comma separated bytes): ");
-                io::stdout().flush().unwrap();
-                io::stdin().read_line(&mut data).unwrap();
-                let data: Vec<u8> = data.trim().split(',').map(|x| x.trim().parse().unwrap()).collect();
-                let id = zkit.batch_and_inscribe(data);
-                println!("Data ingested with ID: {}", id);
+                cli.prompt("Enter data to ingest (comma separated bytes, hex or a JSON array): ")?;
+                io::stdin().read_line(&mut data)?;
+                match parse_ingest_input(&data).and_then(|data| zkit.batch_and_inscribe(data)) {
+                    Ok(id) => cli.result(CliOutput::Ingested { id: id.0 }),
+                    Err(e) => cli.error("ingest data", e),
+                }
             }
             2 => {
-                let proof = zkit.create_proof(&example_circuit).unwrap();
-                println!("Proof created successfully: {:?}", proof);
+                let mut index = String::new();
+                cli.prompt("Enter data ID to prove: ")?;
+                io::stdin().read_line(&mut index)?;
+                let index: u64 = match index.trim().parse() {
+                    Ok(index) => index,
+                    Err(_) => {
+                        cli.invalid("Invalid ID, please try again.");
+                        continue;
+                    }
+                };
+                let task = zkit.spawn_record_proof(index);
+                CANCEL_PROOF.store(false, Ordering::SeqCst);
+                PROVING.store(true, Ordering::SeqCst);
+                let spinner = ['|', '/', '-', '\\'];
+                let mut tick = 0;
+                let outcome = loop {
+                    if CANCEL_PROOF.load(Ordering::SeqCst) {
+                        break None;
+                    }
+                    if let Some(result) = task.wait_timeout(Duration::from_millis(100)) {
+                        break Some(result);
+                    }
+                    cli.prompt(&format!("\rProving {} (Ctrl-C to cancel)", spinner[tick % spinner.len()]))?;
+                    tick += 1;
+                };
+                PROVING.store(false, Ordering::SeqCst);
+                cli.line("");
+
+                match outcome {
+                    Some(Ok(proof)) => cli.result(CliOutput::ProofCreated { proof: hex::encode(proof) }),
+                    Some(Err(e)) => cli.error("create proof", e),
+                    None => {
+                        task.cancel();
+                        cli.result(CliOutput::ProofCancelled);
+                    }
+                }
             }
             3 => {
                 let mut proof = String::new();
-                print!("Enter proof to verify (hex string): ");
-                io::stdout().flush().unwrap();
-                io::stdin().read_line(&mut proof).unwrap();
-                let proof = hex::decode(proof.trim()).unwrap();
-                if zkit.verify_proof(&proof).unwrap() {
-                    println!("Proof verified successfully.");
-                } else {
-                    println!("Proof verification failed.");
+                cli.prompt("Enter proof to verify (hex string): ")?;
+                io::stdin().read_line(&mut proof)?;
+                let proof = match parse_proof_hex(&proof) {
+                    Ok(proof) => proof,
+                    Err(e) => {
+                        cli.error("verify proof", e);
+                        continue;
+                    }
+                };
+                match zkit.verify_proof(&proof) {
+                    Ok(ok) => cli.result(CliOutput::Verified { ok }),
+                    Err(e) => cli.error("verify proof", e),
                 }
             }
             4 => {
                 let mut index = String::new();
-                print!("Enter data ID to retrieve: ");
-                io::stdout().flush().unwrap();
-                io::stdin().read_line(&mut index).unwrap();
-                let index: u64 = index.trim().parse().unwrap();
-                if let Some(data) = zkit.retrieve_data(index) {
-                    println!("Retrieved data: {:?}", data);
-                } else {
-                    println!("Data not found.");
+                cli.prompt("Enter data ID to retrieve: ")?;
+                io::stdin().read_line(&mut index)?;
+                let index: u64 = match index.trim().parse() {
+                    Ok(index) => index,
+                    Err(_) => {
+                        cli.invalid("Invalid ID, please try again.");
+                        continue;
+                    }
+                };
+                match zkit.retrieve_data(RecordId::from(index)) {
+                    Some(data) => cli.result(CliOutput::Retrieved { id: index, data }),
+                    None => cli.result(CliOutput::NotFound { id: index }),
+                }
+            }
+            5 => cli.result(zkit.status().into()),
+            6 => break,
+            7 => {
+                let mut passed = 0;
+                let mut failed = Vec::new();
+                let mut errors = Vec::new();
+                for (id, outcome) in zkit.verify_all() {
+                    match outcome {
+                        Ok(true) => passed += 1,
+                        Ok(false) => failed.push(id),
+                        Err(e) => errors.push((id, e.to_string())),
+                    }
                 }
+                cli.result(CliOutput::VerifiedAll { passed, failed, errors });
+            }
+            _ => cli.invalid("Invalid choice, please try again."),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_snapshots_reads_current_version() {
+        let dir = std::env::temp_dir().join(format!("zkit-diff-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (before, after) = (dir.join("before.zkss"), dir.join("after.zkss"));
+
+        let zkit = ZKIT::builder().k(4).build().unwrap();
+        zkit.insert_with_id(RecordId(1), b"kept".to_vec()).unwrap();
+        zkit.insert_with_id(RecordId(2), b"old".to_vec()).unwrap();
+        zkit.insert_with_id(RecordId(3), b"removed".to_vec()).unwrap();
+        zkit.snapshot(&before).unwrap();
+        assert!(zkit.forget(RecordId(2)));
+        zkit.insert_with_id(RecordId(2), b"new".to_vec()).unwrap();
+        assert!(zkit.forget(RecordId(3)));
+        zkit.insert_with_id(RecordId(4), b"added".to_vec()).unwrap();
+        zkit.snapshot(&after).unwrap();
+
+        let diff = diff_snapshots(&before, &after).unwrap();
+        assert_eq!(diff, SnapshotDiff { added: vec![4], removed: vec![3], changed: vec![2] });
+
+        let encrypted = ZKIT::builder().k(4).encryption_key([7; 32]).build().unwrap();
+        encrypted.insert_with_id(RecordId(1), b"sealed".to_vec()).unwrap();
+        encrypted.snapshot(&after).unwrap();
+        assert!(matches!(diff_snapshots(&before, &after), Err(ZkitError::EncryptedSnapshot)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn trailing_zero_bytes_round_trip() {
+        let data = vec![7, 0, 3, 0, 0];
+        for encoding in [Encoding::Bytewise, Encoding::Packed, Encoding::Dictionary] {
+            for padding in [Padding::Zero, Padding::LengthPrefixed, Padding::Pkcs7] {
+                let zkit = ZKIT::builder().k(4).encoding(encoding).padding(padding).build().unwrap();
+                let id = zkit.batch_and_inscribe(data.clone()).unwrap();
+                assert_eq!(zkit.retrieve_data(id), Some(data.clone()), "{:?} with {:?}", encoding, padding);
             }
-            5 => break,
-            _ => println!("Invalid choice, please try again."),
         }
     }
 }